@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+
+use crate::platform::Platform;
+
+/// File name of a release archive, e.g. "my-rust-game-1.2.0-linux.zip".
+pub fn archive_name(project_name: &str, version: &str, platform: Platform) -> String {
+    format!("{}-{}-{}.zip", slug(project_name), version, platform.dir_name())
+}
+
+/// The project name as a file name, e.g. "My Rust Game" becomes "my-rust-game".
+pub fn slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-")
+}
+
+/// Zips everything exported to `export_dir` into `export_dir/<name>`, except the export log.
+///
+/// The macOS export already is a zip, so it's only renamed instead of being zipped twice.
+pub fn archive_export(export_dir: &Path, export_file: &Path, platform: Platform, name: &str) -> Result<PathBuf> {
+    let archive = export_dir.join(name);
+    if crate::dry_run(format_args!("archive {:?} as {:?}", export_dir, archive)) {
+        return Ok(archive);
+    }
+
+    if platform == Platform::Macos {
+        fs::rename(export_file, &archive)
+            .with_context(|| format!("Failed to rename {:?} to {:?}", export_file, archive))?;
+    } else {
+        // Written next to the files it contains, so earlier archives are left out.
+        let mut files = Vec::new();
+        collect_files(export_dir, &mut files)?;
+        files.retain(|file| file.extension().is_none_or(|ext| ext != "zip"));
+        files.retain(|file| {
+            file.file_name()
+                .is_none_or(|name| name != crate::export_log::LOG_FILE && name != crate::manifest::MANIFEST_FILE)
+        });
+        files.sort();
+
+        let partial = archive.with_extension("part");
+        let _partial_guard = crate::interrupt::temp_path(&partial);
+        let mut zip = zip::ZipWriter::new(fs::File::create(&partial)?);
+        for file in &files {
+            let relative = file.strip_prefix(export_dir)?.to_string_lossy().replace('\\', "/");
+            zip.start_file(relative, file_options(file)?)?;
+            zip.write_all(&fs::read(file)?)?;
+        }
+        zip.finish()?;
+        fs::rename(&partial, &archive)?;
+    }
+
+    let size = fs::metadata(&archive)?.len();
+    info!("Archived {:?} ({})", archive, crate::format_size(size));
+    crate::report::event(
+        "archive",
+        serde_json::json!({ "platform": platform.dir_name(), "path": archive, "size": size }),
+    );
+    Ok(archive)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the executable bit, so the Linux binary still runs after unzipping.
+fn file_options(file: &Path) -> Result<SimpleFileOptions> {
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    #[cfg(unix)]
+    let options = {
+        use std::os::unix::fs::PermissionsExt;
+        options.unix_permissions(fs::metadata(file)?.permissions().mode())
+    };
+    #[cfg(not(unix))]
+    let _ = file;
+    Ok(options)
+}
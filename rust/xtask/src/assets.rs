@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::config::AssetConfig;
+
+/// Copies the `[[assets]]` folders from xtask.toml into the project.
+///
+/// Only files that are missing or older in the project are copied, and files that only
+/// exist in the project are left alone.
+pub fn sync_assets(root: &Path, game_dir: &Path, assets: &[AssetConfig]) -> Result<()> {
+    for asset in assets {
+        let source = root.join(&asset.source);
+        let relative = Path::new(asset.destination.strip_prefix("res://").unwrap_or(&asset.destination));
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            anyhow::bail!("Asset destination '{}' must be a folder inside the project", asset.destination);
+        }
+        let destination = game_dir.join(relative);
+
+        if !source.is_dir() {
+            anyhow::bail!("Asset folder {:?} doesn't exist", source);
+        }
+        if crate::dry_run(format_args!("copy new and changed files from {:?} to {:?}", source, destination)) {
+            continue;
+        }
+
+        let copied = copy_newer(&source, &destination)
+            .with_context(|| format!("Failed to copy assets from {:?}", source))?;
+
+        if copied > 0 {
+            info!("Synced {} asset file(s) from {:?} to {:?}", copied, source, destination);
+        } else {
+            debug!("Assets unchanged: {:?}", destination);
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes `destination` a copy of the project in `source`, copying only what changed and
+/// removing what's gone from the project. Entries named in `keep` are neither copied nor
+/// removed, like the import cache, which stays valid across runs that way.
+pub fn mirror_project(source: &Path, destination: &Path, keep: &[&str]) -> Result<()> {
+    if crate::dry_run(format_args!("mirror {:?} into {:?}", source, destination)) {
+        return Ok(());
+    }
+    let (copied, removed) = mirror(source, destination, keep)
+        .with_context(|| format!("Failed to copy the project {:?} to {:?}", source, destination))?;
+    debug!("Mirrored {:?} into {:?}: {} file(s) copied, {} removed", source, destination, copied, removed);
+    Ok(())
+}
+
+fn mirror(source: &Path, destination: &Path, keep: &[&str]) -> Result<(usize, usize)> {
+    fs::create_dir_all(destination)?;
+    let (mut copied, mut removed) = (0, 0);
+
+    for entry in fs::read_dir(destination)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if keep.iter().any(|kept| name == *kept) || source.join(&name).exists() {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        removed += 1;
+    }
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if keep.iter().any(|kept| name == *kept) {
+            continue;
+        }
+        let (src, dst) = (entry.path(), destination.join(&name));
+        if src.is_dir() {
+            let (dir_copied, dir_removed) = mirror(&src, &dst, &[])?;
+            copied += dir_copied;
+            removed += dir_removed;
+        } else if dst.is_dir() {
+            fs::remove_dir_all(&dst)?;
+            copied += copy_newer_file(&src, &dst)?;
+        } else {
+            copied += copy_newer_file(&src, &dst)?;
+        }
+    }
+    Ok((copied, removed))
+}
+
+/// Copies `src` over `dst` if it's newer, returning 1 if it was copied.
+fn copy_newer_file(src: &Path, dst: &Path) -> Result<usize> {
+    // Followed, so the libraries in bin/ come along even if they're linked into target/.
+    let modified = fs::metadata(src)?.modified()?;
+    let outdated = match fs::metadata(dst) {
+        Ok(existing) => existing.modified()? < modified,
+        Err(_) => true,
+    };
+    if !outdated {
+        return Ok(0);
+    }
+    fs::copy(src, dst)?;
+    // Keep the source time so an unchanged file isn't copied again next time.
+    fs::File::options().write(true).open(dst)?.set_modified(modified)?;
+    Ok(1)
+}
+
+/// Recursively copies the files of `source` that are newer than their copy in `destination`,
+/// returning how many were copied.
+fn copy_newer(source: &Path, destination: &Path) -> Result<usize> {
+    fs::create_dir_all(destination)?;
+
+    let mut copied = 0;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let src = entry.path();
+        let dst = destination.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copied += copy_newer(&src, &dst)?;
+            continue;
+        }
+
+        copied += copy_newer_file(&src, &dst)?;
+    }
+
+    Ok(copied)
+}
@@ -0,0 +1,110 @@
+use log::{debug, warn};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::platform::{Arch, Platform};
+
+/// Enough of the file for every header field read below.
+const HEADER_LEN: usize = 4096;
+
+/// Architectures an executable or library is built for, from its ELF, Mach-O or PE header.
+/// A universal Mach-O has several. `None` for anything unreadable or unknown, e.g. 32-bit x86.
+pub fn read_archs(path: &Path) -> Option<Vec<Arch>> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path).ok()?.take(HEADER_LEN as u64).read_to_end(&mut header).ok()?;
+    let u16_le = |offset: usize| Some(u16::from_le_bytes(header.get(offset..offset + 2)?.try_into().ok()?));
+    let u32_le = |offset: usize| Some(u32::from_le_bytes(header.get(offset..offset + 4)?.try_into().ok()?));
+    let u32_be = |offset: usize| Some(u32::from_be_bytes(header.get(offset..offset + 4)?.try_into().ok()?));
+
+    match header.get(..4)? {
+        [0x7f, b'E', b'L', b'F'] => {
+            // EI_DATA: 1 is little-endian, which is all Godot ships.
+            if header.get(5) != Some(&1) {
+                return None;
+            }
+            let arch = match u16_le(18)? {
+                0x3e => Arch::X86_64,
+                0xb7 => Arch::Arm64,
+                0x28 => Arch::Arm32,
+                _ => return None,
+            };
+            Some(vec![arch])
+        }
+        [0xcf, 0xfa, 0xed, 0xfe] => Some(vec![mach_o_arch(u32_le(4)?)?]),
+        // A universal binary lists every slice, big-endian.
+        [0xca, 0xfe, 0xba, 0xbe] => {
+            let count = u32_be(4)? as usize;
+            (0..count).map(|slice| mach_o_arch(u32_be(8 + slice * 20)?)).collect()
+        }
+        [b'M', b'Z', ..] => {
+            let pe = u32_le(0x3c)? as usize;
+            if header.get(pe..pe + 4)? != b"PE\0\0" {
+                return None;
+            }
+            let arch = match u16_le(pe + 4)? {
+                0x8664 => Arch::X86_64,
+                0xaa64 => Arch::Arm64,
+                0x01c4 => Arch::Arm32,
+                _ => return None,
+            };
+            Some(vec![arch])
+        }
+        _ => None,
+    }
+}
+
+fn mach_o_arch(cpu_type: u32) -> Option<Arch> {
+    match cpu_type {
+        0x0100_0007 => Some(Arch::X86_64),
+        0x0100_000c => Some(Arch::Arm64),
+        12 => Some(Arch::Arm32),
+        _ => None,
+    }
+}
+
+/// Warns when Godot can't load `libraries` because they're built for another CPU than the
+/// editor, which otherwise only shows up as a dlopen error and missing extension classes.
+pub fn check_editor_arch(editor: &Path, platform: Platform, libraries: &[(String, PathBuf)]) {
+    let Some(editor_archs) = read_archs(editor) else {
+        debug!("Can't tell the architecture of {:?}, skipping the check", editor);
+        return;
+    };
+    for (name, library) in libraries {
+        let Some(library_archs) = read_archs(library) else {
+            debug!("Can't tell the architecture of {:?}, skipping the check", library);
+            continue;
+        };
+        if library_archs.iter().any(|arch| editor_archs.contains(arch)) {
+            continue;
+        }
+        let tags = |archs: &[Arch]| archs.iter().map(|arch| arch.tag()).collect::<Vec<_>>().join("+");
+        let editor_arch = editor_archs[0];
+        warn!(
+            "Godot at {:?} is {}, but {} was built for {} ({:?}). Godot won't load the extension and its classes will be missing. \
+             Build with --target {} to match the editor, or install a {} editor.",
+            editor,
+            tags(&editor_archs),
+            name,
+            tags(&library_archs),
+            library,
+            target_triple(platform, editor_arch),
+            tags(&library_archs)
+        );
+    }
+}
+
+/// The Rust target that builds for `platform` on `arch`.
+fn target_triple(platform: Platform, arch: Arch) -> String {
+    let cpu = match arch {
+        Arch::X86_64 => "x86_64",
+        Arch::Arm64 => "aarch64",
+        Arch::Arm32 => "armv7",
+    };
+    let os = match platform {
+        Platform::Windows => "pc-windows-msvc",
+        Platform::Macos => "apple-darwin",
+        _ => "unknown-linux-gnu",
+    };
+    format!("{cpu}-{os}")
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File written into the project, next to project.godot.
+pub const BUILD_INFO_FILE: &str = "version.txt";
+
+/// Writes the commit and time of the build to `version.txt` in the project, so the game can show it
+/// with `FileAccess.get_file_as_string("res://version.txt")`.
+pub fn write_build_info(root: &Path, game_dir: &Path) -> Result<()> {
+    let path = game_dir.join(BUILD_INFO_FILE);
+    let content = format!("{} {}\n", git_revision(root, game_dir), utc_timestamp());
+
+    if crate::dry_run(format_args!("write {:?} ({})", path, content.trim())) {
+        return Ok(());
+    }
+
+    fs::create_dir_all(game_dir)?;
+    fs::write(&path, content)?;
+    info!("Wrote build info to {:?}", path);
+    Ok(())
+}
+
+/// Short hash of HEAD with `-dirty` for uncommitted changes, or "unknown" outside a git checkout.
+pub fn git_revision(root: &Path, game_dir: &Path) -> String {
+    let Some(hash) = git(root, &["rev-parse", "--short", "HEAD"]) else {
+        return "unknown".to_string();
+    };
+    match uncommitted_changes(root, game_dir) {
+        Some(changes) if !changes.is_empty() => format!("{hash}-dirty"),
+        _ => hash,
+    }
+}
+
+/// `git status --porcelain` lines of the workspace, or `None` outside a git checkout.
+pub fn uncommitted_changes(root: &Path, game_dir: &Path) -> Option<Vec<String>> {
+    // The file itself changes on every build, so it must not count as a local change.
+    let relative = game_dir.strip_prefix(root).unwrap_or(game_dir).join(BUILD_INFO_FILE);
+    let exclude = format!(":!{}", relative.display().to_string().replace('\\', "/"));
+    let changes = git(root, &["status", "--porcelain", "--", ".", &exclude])?;
+    Some(changes.lines().map(str::to_string).collect())
+}
+
+fn git(root: &Path, args: &[&str]) -> Option<String> {
+    let mut cmd = Command::new("git");
+    cmd.args(args).current_dir(root);
+    crate::trace_command(&cmd);
+    cmd.output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// For `package`: refuses to export uncommitted changes unless `allow_dirty` is set, so the
+/// commit in version.txt and the manifest is really what shipped.
+pub fn check_clean(root: &Path, game_dir: &Path, allow_dirty: bool) -> Result<()> {
+    let Some(changes) = uncommitted_changes(root, game_dir) else {
+        info!("Not a git checkout, not checking for uncommitted changes");
+        return Ok(());
+    };
+    if changes.is_empty() {
+        return Ok(());
+    }
+    if allow_dirty {
+        warn!("--allow-dirty: packaging {} uncommitted change(s)", changes.len());
+        return Ok(());
+    }
+    let listed = changes.iter().take(20).map(|line| format!("  {}", line)).collect::<Vec<_>>().join("\n");
+    let more = if changes.len() > 20 { format!("\n  ... and {} more", changes.len() - 20) } else { String::new() };
+    anyhow::bail!(
+        "The workspace has uncommitted changes, commit or stash them first, or pass --allow-dirty:\n{}{}",
+        listed,
+        more
+    )
+}
+
+/// Current time as e.g. "2024-05-01T13:37:00Z".
+pub fn utc_timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let (days, time) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
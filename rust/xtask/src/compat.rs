@@ -0,0 +1,58 @@
+use log::{debug, warn};
+
+use crate::config::GodotConfig;
+use crate::metadata::Metadata;
+
+/// Godot versions each gdext (`godot` crate) release line works with, oldest and newest.
+/// Newer editors may still load an older extension, but APIs added since are missing and
+/// loading regularly fails, so anything outside the range gets a warning.
+const GDEXT_COMPATIBILITY: &[(&str, (u32, u32), (u32, u32))] = &[
+    ("0.1", (4, 1), (4, 2)),
+    ("0.2", (4, 1), (4, 4)),
+    ("0.3", (4, 1), (4, 4)),
+    ("0.4", (4, 2), (4, 5)),
+];
+
+/// Warns when the `godot` crate in Cargo.lock is known not to match the configured Godot
+/// version. Never an error: a development gdext may well be newer than this table.
+pub fn check_gdext_version(metadata: &Metadata, godot: &GodotConfig) {
+    let Some(gdext) = metadata.locked_version("godot") else {
+        debug!("No godot crate in Cargo.lock, skipping the gdext version check");
+        return;
+    };
+    let Some(engine) = parse_minor(&godot.features_version()) else {
+        return;
+    };
+    let Some(&(line, oldest, newest)) = GDEXT_COMPATIBILITY.iter().find(|(line, _, _)| same_line(gdext, line)) else {
+        debug!("gdext {} is newer than the compatibility table, assuming it works with Godot {}", gdext, godot.version);
+        return;
+    };
+
+    if engine > newest {
+        // The first release line that supports the configured engine.
+        let recommended = GDEXT_COMPATIBILITY
+            .iter()
+            .find(|(_, _, newest)| engine <= *newest)
+            .map(|(line, _, _)| format!("godot = \"{line}\""))
+            .unwrap_or_else(|| "the latest gdext release (or its master branch)".to_string());
+        warn!(
+            "gdext {} supports Godot up to {}.{}, but Godot {} is configured. The extension may fail to load; update to {}.",
+            gdext, newest.0, newest.1, godot.version, recommended
+        );
+    } else if engine < oldest {
+        warn!(
+            "gdext {} (the {} line) needs at least Godot {}.{}, but Godot {} is configured. Use an older gdext or a newer Godot.",
+            gdext, line, oldest.0, oldest.1, godot.version
+        );
+    }
+}
+
+/// Whether `version` belongs to a release line like "0.4".
+fn same_line(version: &str, line: &str) -> bool {
+    version.strip_prefix(line).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+fn parse_minor(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
@@ -0,0 +1,538 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::platform::{Arch, Platform};
+
+pub const CONFIG_FILE: &str = "xtask.toml";
+
+/// Project settings loaded from `xtask.toml` at the workspace root.
+///
+/// Every key is optional: anything missing falls back to the built-in defaults,
+/// and command line flags take precedence over both.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub godot: GodotConfig,
+    pub build: BuildConfig,
+    pub extension: ExtensionConfig,
+    pub export: ExportConfig,
+    pub project: ProjectConfig,
+    pub macos: MacosConfig,
+    pub windows: WindowsConfig,
+    pub itch: ItchConfig,
+    pub test: TestConfig,
+    pub examples: ExamplesConfig,
+    pub hooks: HooksConfig,
+    /// Folders copied into the project before every build (`[[assets]]`).
+    pub assets: Vec<AssetConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GodotConfig {
+    /// Release tag of the engine, e.g. "4.6-stable".
+    pub version: String,
+    /// Base URL the editor and export templates are downloaded from.
+    pub mirror: String,
+    /// Folder `setup` installs the editors into, one sub-folder per version, relative to the
+    /// workspace root. Point several projects at the same folder to share the installs.
+    pub install_dir: PathBuf,
+    /// Run a Godot found on PATH instead of the one installed in `install_dir`.
+    pub use_system: bool,
+    /// Accept a system Godot whose version differs from `version`.
+    pub force_system: bool,
+    /// Use the .NET (Mono) editor and export templates.
+    pub mono: bool,
+    /// Whether `version` was chosen explicitly (in the file or with --godot-version) rather
+    /// than left at the default.
+    #[serde(skip)]
+    pub version_pinned: bool,
+    /// Whether Godot itself runs with `--verbose`, set by `--godot-verbose`.
+    #[serde(skip)]
+    pub verbose: bool,
+}
+
+impl Default for GodotConfig {
+    fn default() -> Self {
+        Self {
+            version: "4.6-stable".to_string(),
+            mirror: "https://github.com/godotengine/godot/releases/download".to_string(),
+            install_dir: PathBuf::from(".godot_bin"),
+            use_system: false,
+            force_system: false,
+            mono: false,
+            version_pinned: false,
+            verbose: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Extension crates to build. Empty means every cdylib in the workspace.
+    pub crates: Vec<String>,
+    /// Parallel cargo jobs. Unset means cargo's default, one per CPU.
+    pub jobs: Option<u32>,
+}
+
+/// Settings of the generated .gdextension files.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtensionConfig {
+    /// Must match `#[gdextension(entry_symbol = ...)]` when the crate overrides it.
+    pub entry_symbol: String,
+    /// Oldest Godot version allowed to load the extension.
+    pub compatibility_minimum: String,
+    /// Folder inside the project the libraries are installed into, one sub-folder per crate.
+    pub bin_dir: String,
+    /// Let the editor reload the library when it changes. Decided per command, not read from the file.
+    #[serde(skip)]
+    pub reloadable: bool,
+}
+
+impl Default for ExtensionConfig {
+    fn default() -> Self {
+        Self {
+            entry_symbol: "gdext_rust_init".to_string(),
+            compatibility_minimum: "4.1".to_string(),
+            bin_dir: "bin".to_string(),
+            reloadable: false,
+        }
+    }
+}
+
+impl ExtensionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.entry_symbol.is_empty()
+            || !self.entry_symbol.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            anyhow::bail!("'{}' is not a valid entry symbol", self.entry_symbol);
+        }
+
+        // GDExtension exists since 4.0, so this has to look like "4.x" or "4.x.y".
+        let parts: Vec<&str> = self.compatibility_minimum.split('.').collect();
+        let numeric = parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+        if !numeric || !(2..=3).contains(&parts.len()) || parts[0] != "4" {
+            anyhow::bail!(
+                "compatibility_minimum '{}' is not a plausible Godot version (expected e.g. \"4.1\" or \"4.3.0\")",
+                self.compatibility_minimum
+            );
+        }
+
+        // Godot only loads libraries from inside the project.
+        let bin_dir = self.bin_dir();
+        if bin_dir.is_empty() || Path::new(bin_dir).is_absolute() || bin_dir.split('/').any(|part| part == "..") {
+            anyhow::bail!("bin_dir '{}' must be a folder inside the project, e.g. \"bin\" or \"res://bin/dist\"", self.bin_dir);
+        }
+
+        Ok(())
+    }
+
+    /// `bin_dir` relative to the project folder, e.g. "bin/dist" for "res://bin/dist/".
+    pub fn bin_dir(&self) -> &str {
+        self.bin_dir.trim_start_matches("res://").trim_matches('/')
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// Where exported games are written, relative to the workspace root.
+    pub builds_dir: PathBuf,
+    /// Platforms `package` exports when no `--platform` is given. Empty means the host.
+    pub platforms: Vec<Platform>,
+    /// Feature tags written to the `custom_features` of generated presets, checked in the game
+    /// with `OS.has_feature`.
+    pub custom_features: Vec<String>,
+    /// Globs of non-resource files generated presets export too, e.g. "*.json". version.txt is always included.
+    pub include_filter: Vec<String>,
+    /// Globs of files generated presets leave out, e.g. "*.blend" or "tests/*".
+    pub exclude_filter: Vec<String>,
+    /// Files copied next to the exported executable, like shared libraries the extension links
+    /// against (`[[export.extra_files]]`).
+    pub extra_files: Vec<ExtraFile>,
+    /// Base name of the exported executable and pack, e.g. "SuperGame" for SuperGame.exe.
+    /// Defaults to `config/name` of project.godot as a file name, like "super-game".
+    pub binary_name: Option<String>,
+    /// Whether generated presets encrypt the pack, set by `package --encryption-key`. The key
+    /// itself never goes into a file.
+    #[serde(skip)]
+    pub encrypt: bool,
+    /// Which build the presets export, set by `package --server` or `--client`.
+    #[serde(skip)]
+    pub role: Role,
+    /// Whether Windows and Linux exports embed the pack, set by `package --embed-pck` or
+    /// `--no-embed-pck`. Unset keeps what the presets say.
+    #[serde(skip)]
+    pub embed_pck: Option<bool>,
+    /// Templates compiled from source that platforms export with instead of the downloaded
+    /// ones, set by `package --custom-template`. Absolute paths.
+    #[serde(skip)]
+    pub custom_templates: Vec<(Platform, PathBuf)>,
+    /// Architecture of the Windows and Linux exports built for something else than x86_64,
+    /// from `--target` or the host.
+    #[serde(skip)]
+    pub archs: Vec<(Platform, Arch)>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            builds_dir: PathBuf::from("builds"),
+            platforms: Vec::new(),
+            custom_features: Vec::new(),
+            include_filter: Vec::new(),
+            exclude_filter: Vec::new(),
+            extra_files: Vec::new(),
+            binary_name: None,
+            encrypt: false,
+            role: Role::Full,
+            embed_pck: None,
+            custom_templates: Vec::new(),
+            archs: Vec::new(),
+        }
+    }
+}
+
+impl ExportConfig {
+    /// Base name of the exported files. `package` fills it in from project.godot when unset.
+    pub fn binary_name(&self) -> &str {
+        self.binary_name.as_deref().unwrap_or("game")
+    }
+
+    /// The architecture `platform` is exported for, x86_64 unless it was built for another one.
+    pub fn arch(&self, platform: Platform) -> Arch {
+        self.archs.iter().find(|(built, _)| *built == platform).map_or(Arch::X86_64, |(_, arch)| *arch)
+    }
+
+    /// The template `package --custom-template` gave for `platform`.
+    pub fn custom_template(&self, platform: Platform) -> Option<&Path> {
+        self.custom_templates.iter().find(|(custom, _)| *custom == platform).map(|(_, path)| path.as_path())
+    }
+
+    /// `custom_features` of the generated presets, with the tag a client export is told apart by.
+    pub fn preset_features(&self) -> String {
+        let mut features: Vec<&str> = self.custom_features.iter().map(String::as_str).collect();
+        if let Some(tag) = self.role.custom_feature() {
+            if !features.contains(&tag) {
+                features.push(tag);
+            }
+        }
+        features.join(",")
+    }
+}
+
+/// A build of the extension crates with the cargo feature of the same name, installed next to
+/// the regular one as `<crate>_server` or `<crate>_client` so the two don't overwrite each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Role {
+    /// The regular build, used by the editor and every export that isn't a server or client one.
+    #[default]
+    Full,
+    Server,
+    Client,
+}
+
+impl Role {
+    /// Roles built into a library of their own, listed in the .gdextension once installed.
+    pub const SPLIT: [Role; 2] = [Role::Server, Role::Client];
+
+    /// The cargo feature, and the suffix of the installed libraries.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Role::Full => None,
+            Role::Server => Some("server"),
+            Role::Client => Some("client"),
+        }
+    }
+
+    /// Name a crate's libraries are installed under, e.g. "game_server".
+    pub fn library_name(self, crate_name: &str) -> String {
+        match self.name() {
+            Some(suffix) => format!("{crate_name}_{suffix}"),
+            None => crate_name.to_string(),
+        }
+    }
+
+    /// Feature tag only this role's exports have: Godot sets `dedicated_server` for server
+    /// presets, and client presets get `client` as a custom feature.
+    pub fn feature_tag(self) -> Option<&'static str> {
+        match self {
+            Role::Full => None,
+            Role::Server => Some("dedicated_server"),
+            Role::Client => Some("client"),
+        }
+    }
+
+    /// The tag that has to go into `custom_features`, where Godot doesn't set it by itself.
+    pub fn custom_feature(self) -> Option<&'static str> {
+        match self {
+            Role::Client => self.feature_tag(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtraFile {
+    /// File to copy, relative to the workspace root.
+    pub source: PathBuf,
+    /// Platforms it's copied for. Empty means every platform.
+    #[serde(default)]
+    pub platforms: Vec<Platform>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Folder of the Godot project, relative to the workspace root.
+    pub dir: PathBuf,
+    /// `config/name` written to a bootstrapped project.godot.
+    pub name: String,
+    /// `config/icon` written to a bootstrapped project.godot.
+    pub icon: String,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("game"),
+            name: "My Rust Game".to_string(),
+            icon: "res://icon.svg".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MacosConfig {
+    /// `codesign` identity the exported app is signed with, e.g. "Developer ID Application: Name (TEAMID)".
+    /// Nothing is signed when unset.
+    pub sign_identity: Option<String>,
+    /// notarytool keychain profile (see `xcrun notarytool store-credentials`) used by `--notarize`.
+    pub keychain_profile: Option<String>,
+    /// Apple ID used by `--notarize` without a keychain profile. APPLE_ID takes precedence.
+    pub apple_id: Option<String>,
+    /// Developer team id used by `--notarize` without a keychain profile. APPLE_TEAM_ID takes precedence.
+    pub team_id: Option<String>,
+    /// Bundle identifier of the exported app, e.g. "com.studio.game" (com.example.game when unset).
+    pub bundle_identifier: Option<String>,
+    /// Version in the app's Info.plist, defaults to `config/version` in project.godot.
+    pub version: Option<String>,
+    /// App icon, a res:// path to an .icns or a PNG (converted to .icns on macOS).
+    pub icon: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowsConfig {
+    /// .pfx certificate the exported .exe is signed with. WINDOWS_SIGN_CERTIFICATE takes precedence,
+    /// and the password is only read from WINDOWS_SIGN_PASSWORD.
+    pub certificate: Option<PathBuf>,
+    /// SHA-1 thumbprint of a certificate in the Windows certificate store, used instead of a file.
+    /// WINDOWS_SIGN_THUMBPRINT takes precedence.
+    pub thumbprint: Option<String>,
+    /// RFC 3161 timestamp server, so the signature stays valid after the certificate expires.
+    pub timestamp_url: String,
+}
+
+impl Default for WindowsConfig {
+    fn default() -> Self {
+        Self {
+            certificate: None,
+            thumbprint: None,
+            timestamp_url: "http://timestamp.digicert.com".to_string(),
+        }
+    }
+}
+
+/// Where `package --publish` pushes the exports with butler. The API key is only read from
+/// BUTLER_API_KEY, or a `butler login`.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ItchConfig {
+    /// The itch.io project as "user/game", from its URL user.itch.io/game.
+    pub project: Option<String>,
+    /// Channel per platform, e.g. `web = "html5-beta"`. Platforms that aren't listed push to
+    /// windows, linux, mac, android or html5, the names itch.io tags with the platform.
+    pub channels: BTreeMap<String, String>,
+}
+
+impl ItchConfig {
+    /// The channel `platform`'s export is pushed to, with a `-server` or `-client` suffix for
+    /// those builds so they don't replace the full game.
+    pub fn channel(&self, platform: Platform, role: Role) -> String {
+        let channel = self.channels.get(platform.dir_name()).cloned().unwrap_or_else(|| {
+            match platform {
+                Platform::Macos => "mac",
+                Platform::Web => "html5",
+                platform => platform.dir_name(),
+            }
+            .to_string()
+        });
+        match role.name() {
+            Some(role) => format!("{channel}-{role}"),
+            None => channel,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TestConfig {
+    /// Script the `test` command runs with `-s`, e.g. a GUT or gdUnit command line runner.
+    pub script: String,
+    /// Scene to run instead of `script`, for runners that are scenes.
+    pub scene: Option<String>,
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            script: "res://tests/run_tests.gd".to_string(),
+            scene: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExamplesConfig {
+    /// Folder inside the project with the example scenes `run --example` picks from.
+    pub dir: String,
+}
+
+impl Default for ExamplesConfig {
+    fn default() -> Self {
+        Self { dir: "examples".to_string() }
+    }
+}
+
+/// Commands run around builds and exports, each one a program followed by its arguments,
+/// e.g. `pre_build = [["python", "tools/gen_bindings.py"]]`.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Before the Rust crates are built.
+    pub pre_build: Vec<Vec<String>>,
+    /// After the libraries are installed into the project.
+    pub post_build: Vec<Vec<String>>,
+    /// Before `package` imports and exports the project.
+    pub pre_package: Vec<Vec<String>>,
+    /// After every platform was exported.
+    pub post_package: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssetConfig {
+    /// Folder to copy from, relative to the workspace root.
+    pub source: PathBuf,
+    /// Folder inside the project to copy into, e.g. "res/art" or "res://art".
+    pub destination: String,
+}
+
+impl Config {
+    /// Loads `xtask.toml` from `root`, or the defaults when the file doesn't exist.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let mut config: Self = toml::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))?;
+
+        let table: toml::Table = toml::from_str(&content)?;
+        config.godot.version_pinned = table.get("godot").and_then(|godot| godot.get("version")).is_some();
+        Ok(config)
+    }
+}
+
+impl Config {
+    /// The Godot project folder, `game/` unless `project.dir` says otherwise.
+    pub fn game_dir(&self, root: &Path) -> PathBuf {
+        root.join(&self.project.dir)
+    }
+}
+
+impl GodotConfig {
+    /// Folder holding every installed version for the workspace at `root`.
+    pub fn install_root(&self, root: &Path) -> PathBuf {
+        root.join(&self.install_dir)
+    }
+
+    /// Where the configured version is installed, e.g. `.godot_bin/4.6-stable`.
+    pub fn bin_dir(&self, root: &Path) -> PathBuf {
+        self.install_root(root).join(&self.version)
+    }
+
+    /// Engine version as listed in project.godot's `config/features`, e.g. "4.6".
+    pub fn features_version(&self) -> String {
+        let number = self.version.split('-').next().unwrap_or_default();
+        number.split('.').take(2).collect::<Vec<_>>().join(".")
+    }
+
+    /// Name of the folder Godot installs this version's export templates into.
+    pub fn templates_version(&self) -> String {
+        let folder = match self.version.split_once('-') {
+            // "4.6-stable" -> "4.6.0-stable"
+            Some((number, "stable")) if number.matches('.').count() == 1 => format!("{number}.0-stable"),
+            Some((_, "stable")) => self.version.clone(),
+            // Pre-releases are dotted and keep the short number: "4.6-rc1" -> "4.6.rc1", "4.6-dev3" -> "4.6.dev3"
+            Some((number, channel)) => format!("{number}.{channel}"),
+            None => self.version.clone(),
+        };
+
+        // .NET templates live next to the standard ones, with a suffix.
+        if self.mono {
+            format!("{folder}.mono")
+        } else {
+            folder
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates_version(version: &str, mono: bool) -> String {
+        GodotConfig { version: version.to_string(), mono, ..GodotConfig::default() }.templates_version()
+    }
+
+    #[test]
+    fn templates_version_adds_the_patch_number_to_stable_releases() {
+        assert_eq!(templates_version("4.6-stable", false), "4.6.0-stable");
+        assert_eq!(templates_version("4.6.1-stable", false), "4.6.1-stable");
+    }
+
+    #[test]
+    fn templates_version_dots_pre_releases() {
+        assert_eq!(templates_version("4.6-rc1", false), "4.6.rc1");
+        assert_eq!(templates_version("4.6-beta2", false), "4.6.beta2");
+        assert_eq!(templates_version("4.6-dev3", false), "4.6.dev3");
+    }
+
+    #[test]
+    fn templates_version_suffixes_mono() {
+        assert_eq!(templates_version("4.6-stable", true), "4.6.0-stable.mono");
+        assert_eq!(templates_version("4.6.1-stable", true), "4.6.1-stable.mono");
+        assert_eq!(templates_version("4.6-rc1", true), "4.6.rc1.mono");
+    }
+
+    #[test]
+    fn roles_have_their_own_library_and_feature_tag() {
+        assert_eq!(Role::Full.library_name("game"), "game");
+        assert_eq!(Role::Server.library_name("game"), "game_server");
+        assert_eq!(Role::Client.library_name("game"), "game_client");
+        assert_eq!(Role::Full.feature_tag(), None);
+        assert_eq!(Role::Server.feature_tag(), Some("dedicated_server"));
+        assert_eq!(Role::Client.feature_tag(), Some("client"));
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use log::{error, info};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, CONFIG_FILE};
+use crate::metadata::Metadata;
+use crate::platform::Platform;
+
+/// Where a resolved setting came from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File,
+    /// Read from project.godot, like the engine version a project was made with.
+    Project,
+    Flag(&'static str),
+    Env(&'static str),
+}
+
+impl Origin {
+    fn describe(self) -> String {
+        match self {
+            Origin::Default => "default".to_string(),
+            Origin::File => CONFIG_FILE.to_string(),
+            Origin::Project => "project.godot".to_string(),
+            Origin::Flag(flag) => flag.to_string(),
+            Origin::Env(var) => var.to_string(),
+        }
+    }
+}
+
+/// Settings overridden after `xtask.toml` was read, by their key, e.g. "godot.version".
+pub type Origins = BTreeMap<&'static str, Origin>;
+
+/// Prints every setting as it will be used and where it came from, then checks them. Fails
+/// when anything is wrong, after listing all of it.
+pub fn show_config(root: &Path, config: &Config, origins: &Origins) -> Result<()> {
+    let file = file_table(root)?;
+    let resolved = toml::Value::try_from(config).context("Failed to serialize the configuration")?;
+    let mut values = Vec::new();
+    flatten("", &resolved, &mut values);
+
+    let width = values.iter().map(|(key, _)| key.len()).max().unwrap_or_default();
+    for (key, value) in &values {
+        let origin = origins.get(key.as_str()).copied().unwrap_or_else(|| {
+            if file.as_ref().is_some_and(|file| contains(file, key)) {
+                Origin::File
+            } else {
+                Origin::Default
+            }
+        });
+        if crate::report::json() {
+            crate::report::event("config", serde_json::json!({ "key": key, "value": value.to_string(), "origin": origin.describe() }));
+        } else {
+            println!("{:width$} = {}  ({})", key, value, origin.describe());
+        }
+    }
+
+    let problems = validate(root, config);
+    for problem in &problems {
+        error!("{}", problem);
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("{} problem(s) in the configuration", problems.len());
+    }
+    info!("The configuration is valid");
+    Ok(())
+}
+
+fn file_table(root: &Path) -> Result<Option<toml::Table>> {
+    let path = root.join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)?;
+    toml::from_str(&content).map(Some).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+/// One `section.key` per line. Arrays stay whole, an array of tables like `[[assets]]` included.
+fn flatten(prefix: &str, value: &toml::Value, values: &mut Vec<(String, toml::Value)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&key, value, values);
+            }
+        }
+        value => values.push((prefix.to_string(), value.clone())),
+    }
+}
+
+fn contains(table: &toml::Table, key: &str) -> bool {
+    let mut parts = key.split('.');
+    let Some(first) = parts.next() else { return false };
+    let mut value = table.get(first);
+    for part in parts {
+        value = value.and_then(|value| value.get(part));
+    }
+    value.is_some()
+}
+
+fn validate(root: &Path, config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if !is_release_tag(&config.godot.version) {
+        problems.push(format!(
+            "godot.version '{}' is not a Godot release tag (expected e.g. \"4.6-stable\" or \"4.6-rc1\")",
+            config.godot.version
+        ));
+    }
+    match reqwest::Url::parse(&config.godot.mirror) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+        Ok(_) => problems.push(format!("godot.mirror '{}' must be an http:// or https:// URL", config.godot.mirror)),
+        Err(err) => problems.push(format!("godot.mirror '{}' is not a valid URL: {}", config.godot.mirror, err)),
+    }
+    if let Err(err) = config.extension.validate() {
+        problems.push(format!("extension: {}", err));
+    }
+    if let Some(name) = &config.export.binary_name {
+        if let Err(err) = crate::parse_binary_name(name) {
+            problems.push(format!("export.binary_name '{}': {}", name, err));
+        }
+    }
+    // Also fails without any cdylib, when `build.crates` is empty.
+    if let Err(err) = Metadata::load(root).and_then(|metadata| crate::extension_crates(&metadata, &config.build.crates)) {
+        problems.push(format!("build.crates: {:#}", err));
+    }
+    if !config.game_dir(root).is_dir() {
+        problems.push(format!("project.dir: the project folder {:?} doesn't exist", config.project.dir));
+    }
+    for asset in &config.assets {
+        if !root.join(&asset.source).is_dir() {
+            problems.push(format!("assets: the source folder {:?} doesn't exist", asset.source));
+        }
+    }
+    if let Some(project) = &config.itch.project {
+        if let Err(err) = crate::itch::parse_project(project) {
+            problems.push(format!("itch.project '{}': {}", project, err));
+        }
+    }
+    for platform in config.itch.channels.keys() {
+        if Platform::from_str(platform, true).is_err() {
+            problems.push(format!("itch.channels: '{}' isn't a platform (windows, linux, macos, android or web)", platform));
+        }
+    }
+    for file in &config.export.extra_files {
+        if !root.join(&file.source).is_file() {
+            problems.push(format!("export.extra_files: {:?} doesn't exist", file.source));
+        }
+    }
+    problems
+}
+
+/// "4.6-stable", "4.5.1-stable", "4.6-rc1" and the like.
+fn is_release_tag(version: &str) -> bool {
+    let Some((number, channel)) = version.split_once('-') else { return false };
+    let parts: Vec<&str> = number.split('.').collect();
+    let numeric = parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+    let letters = channel.trim_end_matches(|c: char| c.is_ascii_digit());
+    numeric && (2..=3).contains(&parts.len()) && !letters.is_empty() && letters.chars().all(|c| c.is_ascii_lowercase())
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use log::debug;
+use std::path::{Path, PathBuf};
+
+/// Fails early when the file systems `needs` writes to don't have room for it. Needs on the
+/// same file system add up. Paths that can't be checked are skipped, the check is only advice.
+pub fn check_space(needs: &[(PathBuf, u64)]) -> Result<()> {
+    let mut checked: Vec<&Path> = Vec::new();
+    for (path, _) in needs {
+        if checked.iter().any(|other| same_file_system(other, path)) {
+            continue;
+        }
+        checked.push(path);
+
+        let required: u64 = needs.iter().filter(|(other, _)| same_file_system(other, path)).map(|(_, bytes)| bytes).sum();
+        let Some(existing) = existing_ancestor(path) else { continue };
+        let Ok(available) = fs4::available_space(existing) else { continue };
+        debug!(
+            "{:?} needs about {} and has {} available",
+            path,
+            crate::format_size(required),
+            crate::format_size(available)
+        );
+        if available < required {
+            anyhow::bail!(
+                "Not enough disk space for {:?}: about {} needed, only {} available. Free some space, or install elsewhere with --godot-dir.",
+                path,
+                crate::format_size(required),
+                crate::format_size(available)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether `a` and `b` are written to the same file system, as far as that's known.
+pub fn same_file_system(a: &Path, b: &Path) -> bool {
+    let (Some(a), Some(b)) = (existing_ancestor(a), existing_ancestor(b)) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (a.metadata(), b.metadata()) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev(),
+            _ => false,
+        }
+    }
+    // The drive letter is close enough.
+    #[cfg(not(unix))]
+    {
+        let drive = |path: &Path| std::path::absolute(path).ok().and_then(|path| path.components().next().map(|c| c.as_os_str().to_owned()));
+        drive(a) == drive(b)
+    }
+}
+
+/// The folder itself, or the closest parent that exists if it hasn't been created yet.
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.exists())
+}
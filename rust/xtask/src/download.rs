@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use reqwest::blocking::Client;
+use sha2::{Digest, Sha512};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// Folder the downloaded archives are kept in, shared by every project on the machine:
+/// `<cache dir>/rustygodot/<version>/`.
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(dirs::cache_dir().context("Could not find the user cache directory")?.join("rustygodot"))
+}
+
+/// Deletes every cached archive.
+pub fn clear_cache() -> Result<()> {
+    let dir = cache_dir()?;
+    if dir.exists() && !crate::dry_run(format_args!("delete {:?}", dir)) {
+        fs::remove_dir_all(&dir)?;
+        info!("Cleared download cache at {:?}", dir);
+    }
+    Ok(())
+}
+
+/// Returns the zip archive found at `url`, reusing the cached copy of `version` unless
+/// `use_cache` is false. A fresh download is checked against the release's SHA512-SUMS.txt
+/// and written to the cache either way.
+pub fn fetch_archive(url: &str, version: &str, use_cache: bool) -> Result<Vec<u8>> {
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    let cached = cache_dir()?.join(version).join(file_name);
+    let cached_sum = cached.with_extension("sha512");
+
+    // Offline, the cache is all there is.
+    if (use_cache || crate::offline()) && cached.exists() {
+        let bytes = fs::read(&cached)?;
+        let valid = match fs::read_to_string(&cached_sum) {
+            Ok(expected) => sha512_hex(&bytes) == expected.trim(),
+            // Without a known checksum, at least make sure it isn't cut off: an interrupted
+            // download has no central directory at the end, so it won't open.
+            Err(_) => zip::ZipArchive::new(Cursor::new(&bytes)).is_ok(),
+        };
+        if valid {
+            info!("Using cached {:?}", cached);
+            return Ok(bytes);
+        }
+        info!("Cached {:?} is damaged, downloading it again", cached);
+    }
+
+    if crate::offline() {
+        anyhow::bail!("offline: {} not available locally (looked in {:?})", file_name, cached);
+    }
+
+    let client = client()?;
+    let bytes = get(&client, url, Some(version))?;
+
+    let checksum = sha512_hex(&bytes);
+    match published_checksum(&client, url) {
+        Some(expected) if expected != checksum => anyhow::bail!(
+            "Checksum mismatch for {}: expected SHA-512 {}, got {}. The download is corrupt, please try again.",
+            file_name,
+            expected,
+            checksum
+        ),
+        Some(_) => info!("Verified SHA-512 of {}", file_name),
+        None => warn!("No published SHA-512 for {}, skipping verification", file_name),
+    }
+
+    // Written under a temporary name first so a crash never leaves a truncated cache entry.
+    if let Some(dir) = cached.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial = cached.with_extension("part");
+    let _partial_guard = crate::interrupt::temp_path(&partial);
+    fs::write(&partial, &bytes)?;
+    fs::rename(&partial, &cached)?;
+    fs::write(&cached_sum, &checksum)?;
+
+    Ok(bytes)
+}
+
+/// Size of the archive from `url` in the cache, if it's there.
+pub fn cached_size(url: &str, version: &str) -> Option<u64> {
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    fs::metadata(cache_dir().ok()?.join(version).join(file_name)).ok().map(|metadata| metadata.len())
+}
+
+/// Size of the file at `url` according to the server, without downloading it.
+pub fn remote_size(url: &str) -> Option<u64> {
+    if crate::offline() {
+        return None;
+    }
+    let response = client().ok()?.head(url).send().ok()?.error_for_status().ok()?;
+    // Read from the header: for a HEAD request, the body reqwest sees is always empty.
+    response.headers().get(reqwest::header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// SHA-512 of the cached archive downloaded from `url`, if it's in the cache.
+pub fn cached_checksum(url: &str, version: &str) -> Option<String> {
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    let cached = cache_dir().ok()?.join(version).join(file_name);
+    match fs::read_to_string(cached.with_extension("sha512")) {
+        Ok(checksum) => Some(checksum.trim().to_string()),
+        Err(_) => fs::read(&cached).ok().map(|bytes| sha512_hex(&bytes)),
+    }
+}
+
+/// Downloads anything that isn't a Godot release, like the xtask binary for `self-update`.
+pub fn fetch(url: &str) -> Result<Vec<u8>> {
+    if crate::offline() {
+        anyhow::bail!("offline: can't download {}", url);
+    }
+    get(&client()?, url, None)
+}
+
+fn client() -> Result<Client> {
+    Ok(Client::builder()
+        .timeout(None) // Disable timeout completely for large files
+        .build()?)
+}
+
+/// The body of `url`, with advice on what to do when it can't be downloaded. `version` is
+/// the Godot release being downloaded, if it is one.
+fn get(client: &Client, url: &str, version: Option<&str>) -> Result<Vec<u8>> {
+    info!("Downloading {}", url);
+    let bytes = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .map_err(|err| {
+            let advice = download_advice(&err, version);
+            anyhow::Error::new(err).context(format!("Failed to download {}\n{}", url, advice))
+        })?;
+    Ok(bytes.to_vec())
+}
+
+/// The SHA-512 listed for the file at `url` in the SHA512-SUMS.txt next to it, which Godot
+/// publishes with every release. Mirrors may not have one.
+fn published_checksum(client: &Client, url: &str) -> Option<String> {
+    let (base, file_name) = url.rsplit_once('/')?;
+    let sums = client
+        .get(format!("{base}/SHA512-SUMS.txt"))
+        .send()
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .text()
+        .ok()?;
+
+    // Lines look like "<hash>  <file name>", like sha512sum's output.
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file_name).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// What to do about a failed download, depending on how it failed.
+fn download_advice(err: &reqwest::Error, version: Option<&str>) -> String {
+    // reqwest doesn't classify DNS and TLS failures, but the underlying errors name them.
+    let details = std::iter::successors(std::error::Error::source(err), |source| source.source())
+        .map(|source| source.to_string().to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if err.status() == Some(reqwest::StatusCode::NOT_FOUND) {
+        let Some(version) = version else {
+            return "The server has no such file (404). Check the URL.".to_string();
+        };
+        format!(
+            "The mirror has no such file (404). Check that '{version}' is a real release tag \
+             (e.g. 4.6-stable or 4.6-rc1) and that `mirror` in xtask.toml is right."
+        )
+    } else if let Some(status) = err.status() {
+        format!("The server answered {status}. Try again later, or set `mirror` in xtask.toml to another mirror.")
+    } else if details.contains("dns") || details.contains("resolve") {
+        "The host name could not be resolved. Check your internet connection and DNS, \
+         or use --offline if the files are already cached."
+            .to_string()
+    } else if details.contains("certificate") || details.contains("tls") || details.contains("ssl") {
+        "The server's TLS certificate was rejected. A proxy that inspects HTTPS needs its certificate \
+         installed in the system trust store."
+            .to_string()
+    } else if err.is_timeout() {
+        "The download timed out. Check your connection, or retry with a closer `mirror`.".to_string()
+    } else if err.is_connect() {
+        "Could not connect. Check your internet connection, set HTTPS_PROXY if you're behind a proxy, \
+         or use --offline if the files are already cached."
+            .to_string()
+    } else {
+        "The download was interrupted. Try again, or use --offline if the files are already cached.".to_string()
+    }
+}
+
+fn sha512_hex(bytes: &[u8]) -> String {
+    Sha512::digest(bytes).iter().map(|byte| format!("{byte:02x}")).collect()
+}
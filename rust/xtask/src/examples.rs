@@ -0,0 +1,53 @@
+use anyhow::Result;
+use log::info;
+use std::fs;
+use std::path::Path;
+
+use crate::config::ExamplesConfig;
+
+/// Names of the example scenes, the `.tscn` files directly in the examples folder.
+fn example_names(game_dir: &Path, examples: &ExamplesConfig) -> Result<Vec<String>> {
+    let dir = game_dir.join(examples.dir.trim_start_matches("res://"));
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tscn"))
+        .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+pub fn list_examples(game_dir: &Path, examples: &ExamplesConfig) -> Result<()> {
+    let names = example_names(game_dir, examples)?;
+    if names.is_empty() {
+        info!("No example scenes in res://{}/", examples.dir.trim_start_matches("res://"));
+        return Ok(());
+    }
+    for name in &names {
+        if crate::report::json() {
+            crate::report::event("example", serde_json::json!({ "name": name, "scene": scene_path(examples, name) }));
+        } else {
+            println!("{}", name);
+        }
+    }
+    Ok(())
+}
+
+/// The res:// path of the example called `name`.
+pub fn scene(game_dir: &Path, examples: &ExamplesConfig, name: &str) -> Result<String> {
+    let names = example_names(game_dir, examples)?;
+    if !names.iter().any(|example| example == name) {
+        if names.is_empty() {
+            anyhow::bail!("No example '{}': there are no scenes in res://{}/", name, examples.dir.trim_start_matches("res://"));
+        }
+        anyhow::bail!("No example '{}' (available: {})", name, names.join(", "));
+    }
+    Ok(scene_path(examples, name))
+}
+
+fn scene_path(examples: &ExamplesConfig, name: &str) -> String {
+    format!("res://{}/{}.tscn", examples.dir.trim_start_matches("res://").trim_end_matches('/'), name)
+}
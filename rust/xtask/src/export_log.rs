@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Written next to every export, with everything Godot printed while exporting.
+pub const LOG_FILE: &str = "export.log";
+
+/// How many errors and warnings the summary shows, the rest are only in the log file.
+const SHOWN: usize = 5;
+
+/// Runs Godot and returns its exit status with its stdout and stderr, in the order the lines
+/// came in. The output is only printed with `-v`.
+pub fn run_captured(cmd: &mut Command) -> Result<(ExitStatus, String)> {
+    let (status, log) = run_captured_with_timeout(cmd, None)?;
+    Ok((status.context("Godot stopped without an exit status")?, log))
+}
+
+/// Like `run_captured`, but kills the process, and what it started, once `timeout` has passed
+/// and returns no exit status then.
+pub fn run_captured_with_timeout(cmd: &mut Command, timeout: Option<Duration>) -> Result<(Option<ExitStatus>, String)> {
+    let mut log = String::new();
+    let status = run_lines(cmd, timeout, |_, line| {
+        log.push_str(line);
+        log.push('\n');
+    })?;
+    Ok((status, log))
+}
+
+/// Like `run_captured_with_timeout`, with every line paired with how long after the start
+/// xtask received it.
+pub fn run_timed(cmd: &mut Command, timeout: Option<Duration>) -> Result<(Option<ExitStatus>, Vec<(Duration, String)>)> {
+    let mut lines = Vec::new();
+    let status = run_lines(cmd, timeout, |elapsed, line| lines.push((elapsed, line.to_string())))?;
+    Ok((status, lines))
+}
+
+fn run_lines(cmd: &mut Command, timeout: Option<Duration>, mut on_line: impl FnMut(Duration, &str)) -> Result<Option<ExitStatus>> {
+    let started = Instant::now();
+    if timeout.is_some() {
+        crate::interrupt::isolate_group(cmd);
+    }
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to launch Godot")?;
+    let _tracked = crate::interrupt::track(&child);
+
+    let (tx, rx) = mpsc::channel::<String>();
+    let streams: [Option<Box<dyn Read + Send>>; 2] = [
+        child.stdout.take().map(|stream| Box::new(stream) as Box<dyn Read + Send>),
+        child.stderr.take().map(|stream| Box::new(stream) as Box<dyn Read + Send>),
+    ];
+    for stream in streams.into_iter().flatten() {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let deadline = timeout.map(|timeout| started + timeout);
+    loop {
+        let line = match deadline {
+            Some(deadline) => match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => line,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    crate::interrupt::kill_tree(&mut child);
+                    return Ok(None);
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            },
+            None => match rx.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            },
+        };
+        debug!("[godot] {}", line);
+        on_line(started.elapsed(), &line);
+    }
+    Ok(Some(child.wait()?))
+}
+
+/// Prints how many errors and warnings Godot reported and the first few of each, returning
+/// both counts. An export can succeed and still be missing resources, which only shows here.
+pub fn summarize(log: &str, log_path: &Path) -> (usize, usize) {
+    let (errors, warnings) = errors_and_warnings(log);
+
+    if errors.is_empty() && warnings.is_empty() {
+        debug!("Godot reported no errors or warnings, the full log is in {:?}", log_path);
+        return (0, 0);
+    }
+    warn!("Godot reported {} error(s) and {} warning(s), the full log is in {:?}", errors.len(), warnings.len(), log_path);
+    for message in errors.iter().take(SHOWN) {
+        warn!("  ERROR: {}", message);
+    }
+    for message in warnings.iter().take(SHOWN) {
+        info!("  WARNING: {}", message);
+    }
+    if errors.len() > SHOWN || warnings.len() > SHOWN {
+        info!("  ...");
+    }
+    (errors.len(), warnings.len())
+}
+
+/// The messages of the `ERROR:` and `SCRIPT ERROR:` lines, then of the `WARNING:` lines.
+pub fn errors_and_warnings(log: &str) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for line in log.lines().map(strip_ansi) {
+        let line = line.trim();
+        if let Some(message) = line.strip_prefix("ERROR:").or_else(|| line.strip_prefix("SCRIPT ERROR:")) {
+            errors.push(message.trim().to_string());
+        } else if let Some(message) = line.strip_prefix("WARNING:") {
+            warnings.push(message.trim().to_string());
+        }
+    }
+    (errors, warnings)
+}
+
+/// The last `lines` lines of the log, for error messages.
+pub fn tail(log: &str, lines: usize) -> String {
+    let all: Vec<&str> = log.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Removes terminal color codes, which Godot adds to its errors and warnings.
+pub fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skips "[1;31m" and the like, up to the final letter.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+use zip::ZipArchive;
+
+/// Extracts a zip held in memory into `dest`, decompressing the entries on every core.
+/// Anything the parallel path can't handle falls back to a sequential extraction.
+///
+/// Every entry has to stay inside `dest`: a poisoned mirror or cache could otherwise ship
+/// `../` or absolute paths that write anywhere on the machine.
+pub fn extract(bytes: &[u8], dest: &Path) -> Result<()> {
+    let started = Instant::now();
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).context("Failed to open the archive")?;
+    // Checked up front, so a bad archive writes nothing at all.
+    for index in 0..archive.len() {
+        entry_path(dest, archive.by_index_raw(index)?.name())?;
+    }
+
+    // The macOS editor has symlinks in its frameworks, which only the sequential path recreates.
+    if has_symlinks(&mut archive)? {
+        debug!("The archive contains symlinks, extracting sequentially");
+        extract_sequential(&mut archive, dest)?;
+    } else if let Err(err) = extract_parallel(&archive, dest) {
+        warn!("Parallel extraction failed ({:#}), extracting sequentially", err);
+        extract_sequential(&mut archive, dest)?;
+    }
+
+    debug!("Extracted {} entries in {:.1}s", archive.len(), started.elapsed().as_secs_f32());
+    Ok(())
+}
+
+fn extract_parallel(archive: &ZipArchive<Cursor<&[u8]>>, dest: &Path) -> Result<()> {
+    let mut archive = archive.clone();
+
+    // Every folder is created up front, so the threads never race to create the same one.
+    let mut files = Vec::new();
+    let mut dirs = BTreeSet::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let path = entry_path(dest, entry.name())?;
+        if entry.is_dir() {
+            dirs.insert(path);
+        } else {
+            if let Some(parent) = path.parent() {
+                dirs.insert(parent.to_path_buf());
+            }
+            files.push(index);
+        }
+    }
+    for dir in &dirs {
+        fs::create_dir_all(dir)?;
+    }
+
+    let threads = std::thread::available_parallelism().map_or(4, |threads| threads.get()).min(files.len().max(1));
+    debug!("Extracting {} files on {} threads", files.len(), threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|worker| {
+                // Every thread reads through its own handle of the same in-memory archive.
+                let mut archive = archive.clone();
+                let files = files.iter().skip(worker).step_by(threads).copied().collect::<Vec<_>>();
+                scope.spawn(move || -> Result<()> {
+                    for index in files {
+                        let mut entry = archive.by_index(index)?;
+                        let path = entry_path(dest, entry.name())?;
+                        let mode = entry.unix_mode();
+                        write_file(&mut entry, mode, &path)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().unwrap_or_else(|_| Err(anyhow::anyhow!("An extraction thread panicked"))))
+    })
+}
+
+/// Extracts one entry after the other, recreating symlinks as long as they point inside `dest`.
+fn extract_sequential(archive: &mut ZipArchive<Cursor<&[u8]>>, dest: &Path) -> Result<()> {
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let path = entry_path(dest, entry.name())?;
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            let name = entry.name().to_string();
+            write_symlink(dest, &path, &target).with_context(|| format!("Refusing to extract the symlink {} -> {}", name, target))?;
+        } else {
+            let mode = entry.unix_mode();
+            write_file(&mut entry, mode, &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the entry called `name` goes in `dest`. Absolute paths, drive letters and `..` are
+/// rejected rather than skipped, the archive can't be trusted then.
+fn entry_path(dest: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    let mut path = dest.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => anyhow::bail!(
+                "The archive has an entry outside the install folder: {}. The download may have been tampered with, use --clear-cache and try again.",
+                name
+            ),
+        }
+    }
+    // Names like "C:foo" or "..\\foo" are plain file names on Linux, but escape on Windows.
+    if name.contains('\\') || name.contains(':') {
+        anyhow::bail!("The archive has an entry with an unsafe name: {}", name);
+    }
+    Ok(path)
+}
+
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn write_file(entry: &mut impl Read, mode: Option<u32>, path: &Path) -> Result<()> {
+    let mut file = fs::File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+    io::copy(entry, &mut file)?;
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    Ok(())
+}
+
+fn write_symlink(dest: &Path, link: &Path, target: &str) -> Result<()> {
+    // The target is relative to the link's folder, and has to land inside `dest` too.
+    let mut resolved = link.parent().unwrap_or(dest).to_path_buf();
+    for component in Path::new(target).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::RootDir | Component::Prefix(_) => anyhow::bail!("it's absolute"),
+        }
+    }
+    if !resolved.starts_with(dest) {
+        anyhow::bail!("it points outside the install folder");
+    }
+
+    #[cfg(unix)]
+    {
+        if link.symlink_metadata().is_ok() {
+            fs::remove_file(link)?;
+        }
+        std::os::unix::fs::symlink(target, link)?;
+    }
+    // Only the macOS editor has symlinks, and it isn't run from Windows.
+    #[cfg(not(unix))]
+    warn!("Skipping the symlink {:?}", link);
+    Ok(())
+}
+
+fn has_symlinks(archive: &mut ZipArchive<Cursor<&[u8]>>) -> Result<bool> {
+    for index in 0..archive.len() {
+        if archive.by_index_raw(index)?.is_symlink() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,186 @@
+use anyhow::Result;
+use log::info;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{ExtensionConfig, Role};
+use crate::platform::Platform;
+
+/// Writes `<crate_name>.gdextension` into the project, pointing every platform/architecture
+/// at `<bin_dir>/<crate_name>/<platform>/<arch>/<debug|release>/`.
+///
+/// Installed server and client builds (`<crate_name>_server` and `<crate_name>_client`) are
+/// listed in the same file under their feature tag. Godot loads every .gdextension in the
+/// project, so a file of their own would register the same classes twice.
+pub fn generate_gdextension_file(
+    game_dir: &Path,
+    crate_name: &str,
+    settings: &ExtensionConfig,
+    macos_universal: bool,
+) -> Result<()> {
+    let gdext_path = game_dir.join(format!("{}.gdextension", crate_name));
+
+    let mut libraries = String::new();
+    for (platform, arch, folder) in library_slots(macos_universal) {
+        for role in [Role::Full].into_iter().chain(Role::SPLIT) {
+            let library_name = role.library_name(crate_name);
+            let file_name = platform.library_file_name(&library_name);
+            let bin = format!("{}/{library_name}/{folder}", settings.bin_dir());
+            let exists = |variant: &str| game_dir.join(&bin).join(variant).join(&file_name).exists();
+            // A server or client entry would win over the regular one even without its library.
+            if role != Role::Full && !exists("debug") && !exists("release") {
+                continue;
+            }
+
+            for (variant, fallback) in [("debug", "release"), ("release", "debug")] {
+                // Fall back to the other variant when only one was ever built, so running the
+                // editor after a release-only build (or the reverse) still finds a library.
+                let variant_dir = if !exists(variant) && exists(fallback) { fallback } else { variant };
+
+                let mut feature = match arch {
+                    Some(arch) => format!("{}.{variant}.{arch}", platform.dir_name()),
+                    None => format!("{}.{variant}", platform.dir_name()),
+                };
+                if let Some(tag) = role.feature_tag() {
+                    feature = format!("{feature}.{tag}");
+                }
+                libraries.push_str(&format!("{feature} = \"res://{bin}/{variant_dir}/{file_name}\"\n"));
+            }
+        }
+    }
+
+    let entry_symbol = &settings.entry_symbol;
+    let compatibility_minimum = &settings.compatibility_minimum;
+    // Hot reload costs a little at runtime, so it's only turned on for editor sessions.
+    let reloadable = if settings.reloadable { "\nreloadable = true" } else { "" };
+    let content = format!(r#"
+[configuration]
+entry_symbol = "{entry_symbol}"
+compatibility_minimum = "{compatibility_minimum}"{reloadable}
+
+[libraries]
+{libraries}"#);
+
+    // Rewriting an identical file still makes the editor reload the extension, and on Windows
+    // fails while Godot has it open.
+    let content = content.trim();
+    if fs::read_to_string(&gdext_path).is_ok_and(|existing| existing == content) {
+        info!(".gdextension file unchanged at: {:?}", gdext_path);
+        return Ok(());
+    }
+    if crate::dry_run(format_args!("write {:?}", gdext_path)) {
+        return Ok(());
+    }
+    fs::write(&gdext_path, content)?;
+    info!("Generated .gdextension file at: {:?}", gdext_path);
+
+    Ok(())
+}
+
+/// Platform, architecture feature tag and bin sub-folder of every library the file lists.
+fn library_slots(macos_universal: bool) -> Vec<(Platform, Option<&'static str>, &'static str)> {
+    let mut slots = vec![
+        (Platform::Linux, Some("x86_64"), "linux/x86_64"),
+        (Platform::Linux, Some("arm64"), "linux/arm64"),
+        // 32-bit Raspberry Pi OS and the like, built with --target armv7-unknown-linux-gnueabihf.
+        (Platform::Linux, Some("arm32"), "linux/arm32"),
+    ];
+
+    // Godot picks the entry matching the most feature tags, so a universal dylib must be the only
+    // macOS entry or the arch-specific lines would win.
+    if macos_universal {
+        slots.push((Platform::Macos, None, "macos/universal"));
+    } else {
+        slots.push((Platform::Macos, Some("arm64"), "macos/arm64"));
+        slots.push((Platform::Macos, Some("x86_64"), "macos/x86_64"));
+    }
+
+    slots.extend([
+        (Platform::Windows, Some("x86_64"), "windows/x86_64"),
+        (Platform::Windows, Some("arm64"), "windows/arm64"),
+        (Platform::Android, Some("arm64"), "android/arm64"),
+        (Platform::Android, Some("arm32"), "android/arm32"),
+        (Platform::Android, Some("x86_64"), "android/x86_64"),
+        (Platform::Web, Some("wasm32"), "web"),
+        // A static library for arm64 devices, the only iOS builds Godot ships templates for.
+        (Platform::Ios, None, "ios/arm64"),
+    ]);
+
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    /// An empty project folder of its own for every test.
+    fn game_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xtask-gdextension-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unchanged_file_is_not_rewritten() {
+        let game_dir = game_dir("unchanged");
+        let settings = ExtensionConfig::default();
+        let path = game_dir.join("game.gdextension");
+
+        generate_gdextension_file(&game_dir, "game", &settings, false).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        // Moved into the past, a rewrite within the same second would otherwise go unnoticed.
+        let written = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(written).unwrap();
+
+        generate_gdextension_file(&game_dir, "game", &settings, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), written);
+        fs::remove_dir_all(&game_dir).unwrap();
+    }
+
+    #[test]
+    fn every_linux_architecture_is_listed() {
+        let game_dir = game_dir("linux-archs");
+        generate_gdextension_file(&game_dir, "game", &ExtensionConfig::default(), false).unwrap();
+        let content = fs::read_to_string(game_dir.join("game.gdextension")).unwrap();
+        for arch in ["x86_64", "arm64", "arm32"] {
+            let line = format!("linux.release.{arch} = \"res://bin/game/linux/{arch}/release/libgame.so\"");
+            assert!(content.lines().any(|entry| entry == line), "missing {} in\n{}", line, content);
+        }
+        fs::remove_dir_all(&game_dir).unwrap();
+    }
+
+    #[test]
+    fn server_and_client_builds_are_listed_under_their_feature_tag() {
+        let game_dir = game_dir("roles");
+        let settings = ExtensionConfig::default();
+        // Only release builds for Linux, as `package --server` and `package --client` install them.
+        for role in Role::SPLIT {
+            let library_name = role.library_name("game");
+            let dir = game_dir.join("bin").join(&library_name).join("linux/x86_64/release");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(Platform::Linux.library_file_name(&library_name)), "").unwrap();
+        }
+
+        generate_gdextension_file(&game_dir, "game", &settings, false).unwrap();
+        let content = fs::read_to_string(game_dir.join("game.gdextension")).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        for (library_name, tag) in [("game_server", "dedicated_server"), ("game_client", "client")] {
+            let file_name = Platform::Linux.library_file_name(library_name);
+            for variant in ["debug", "release"] {
+                // The debug entry falls back to the only build there is.
+                let line = format!("linux.{variant}.x86_64.{tag} = \"res://bin/{library_name}/linux/x86_64/release/{file_name}\"");
+                assert!(lines.contains(&line.as_str()), "missing {} in\n{}", line, content);
+            }
+        }
+        // Neither role was built for anything else.
+        assert!(!content.contains("windows.release.x86_64.dedicated_server"));
+        assert!(!content.contains("linux.release.arm64.client"));
+        // The regular library stays listed without a tag.
+        assert!(lines.iter().any(|line| line.starts_with("linux.release.x86_64 = ")));
+        fs::remove_dir_all(&game_dir).unwrap();
+    }
+}
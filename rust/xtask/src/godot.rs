@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::Config;
+use crate::get_os_info;
+
+/// Finds the Godot executable to run: the one `setup` installed in `install_dir`, or one
+/// from the system when `use_system` is set or nothing has been installed yet.
+pub fn godot_executable(root: &Path, config: &Config) -> Result<PathBuf> {
+    let (_, bin_relative_path) = get_os_info(&config.godot);
+    let installed = config.godot.bin_dir(root).join(bin_relative_path);
+
+    if !config.godot.use_system && installed.exists() {
+        return Ok(installed);
+    }
+
+    match find_system_godot() {
+        Some(system) => {
+            check_system_version(&system, config)?;
+            Ok(system)
+        }
+        None if config.godot.use_system => anyhow::bail!("No godot or godot4 executable found on PATH."),
+        None => anyhow::bail!(
+            "Godot executable not found. Run 'cargo xtask setup' first, or put Godot on PATH and pass --use-system."
+        ),
+    }
+}
+
+/// Versions that have an editor installed in `install_dir`, sorted by name.
+pub fn installed_versions(root: &Path, config: &Config) -> Result<Vec<String>> {
+    let install_root = config.godot.install_root(root);
+    if !install_root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions = Vec::new();
+    for entry in std::fs::read_dir(&install_root)? {
+        let entry = entry?;
+        let version = entry.file_name().to_string_lossy().to_string();
+        let mut godot = config.godot.clone();
+        godot.version = version.clone();
+        // Either build of the editor counts.
+        let installed = [false, true].iter().any(|&mono| {
+            godot.mono = mono;
+            entry.path().join(get_os_info(&godot).1).exists()
+        });
+        if installed {
+            versions.push(version);
+        }
+    }
+
+    versions.sort();
+    Ok(versions)
+}
+
+/// Engine version the project was made with, from the `config/features` line of its
+/// project.godot, e.g. "4.6".
+pub fn project_engine_version(game_dir: &Path) -> Option<String> {
+    let project = std::fs::read_to_string(game_dir.join("project.godot")).ok()?;
+    let features = project.lines().find_map(|line| line.trim().strip_prefix("config/features="))?;
+
+    // config/features=PackedStringArray("4.6", "Forward Plus")
+    features
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .find(|feature| feature.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())))
+        .map(str::to_string)
+}
+
+/// Replaces the engine version `from` in the `config/features` line of project.godot with `to`,
+/// leaving every other line and feature as it is.
+pub fn set_project_engine_version(game_dir: &Path, from: &str, to: &str) -> Result<()> {
+    let path = game_dir.join("project.godot");
+    let project = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    let quoted = format!("\"{from}\"");
+    let updated: String = project
+        .split_inclusive('\n')
+        .map(|line| {
+            if line.trim_start().starts_with("config/features=") {
+                line.replacen(&quoted, &format!("\"{to}\""), 1)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    std::fs::write(&path, updated).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// A string setting of the game's project.godot, e.g. `config/version`, if it's set.
+pub fn project_setting(game_dir: &Path, key: &str) -> Option<String> {
+    let project = std::fs::read_to_string(game_dir.join("project.godot")).ok()?;
+    project
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))
+        .map(|version| version.trim_matches('"').to_string())
+        .filter(|version| !version.is_empty())
+}
+
+fn find_system_godot() -> Option<PathBuf> {
+    let names: &[&str] = if cfg!(target_os = "windows") {
+        &["godot4.exe", "godot.exe"]
+    } else {
+        &["godot4", "godot"]
+    };
+
+    let path = std::env::var_os("PATH")?;
+    let from_path = std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file());
+
+    // The macOS app bundle doesn't put anything on PATH.
+    from_path
+        .or_else(|| {
+            let app = PathBuf::from("/Applications/Godot.app/Contents/MacOS/Godot");
+            (cfg!(target_os = "macos") && app.is_file()).then_some(app)
+        })
+        .or_else(|| cfg!(target_os = "linux").then(find_sandboxed_godot).flatten())
+}
+
+/// Godot installed from Flathub or the Snap Store. Both export a launcher that wraps the sandbox
+/// (`flatpak run org.godotengine.Godot "$@"` for Flatpak), so it can be run like a plain binary.
+fn find_sandboxed_godot() -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(data_dir) = dirs::data_dir() {
+        candidates.push(data_dir.join("flatpak/exports/bin/org.godotengine.Godot"));
+    }
+    candidates.push(PathBuf::from("/var/lib/flatpak/exports/bin/org.godotengine.Godot"));
+    candidates.push(PathBuf::from("/snap/bin/godot-4"));
+    candidates.push(PathBuf::from("/snap/bin/godot"));
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Runs `godot --version --headless` and returns what it reports, e.g. "4.6.stable.official.89cea1439".
+pub fn reported_version(godot: &Path) -> Result<String> {
+    let mut cmd = Command::new(godot);
+    cmd.arg("--version").arg("--headless");
+    crate::trace_command(&cmd);
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run {:?} --version", godot))?;
+    if !output.status.success() {
+        anyhow::bail!("{:?} --version failed ({})", godot, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Whether a `--version` output belongs to the configured release tag.
+pub fn is_configured_version(reported: &str, config: &Config) -> bool {
+    // Godot reports e.g. "4.6.stable.official.89cea1439" for the "4.6-stable" tag.
+    reported.starts_with(&config.godot.version.replace('-', "."))
+}
+
+fn check_system_version(godot: &Path, config: &Config) -> Result<()> {
+    let reported = reported_version(godot)?;
+
+    if is_configured_version(&reported, config) {
+        info!("Using system Godot {} at {:?}", reported, godot);
+        return Ok(());
+    }
+
+    if !config.godot.force_system {
+        anyhow::bail!(
+            "System Godot at {:?} reports version {}, but {} is configured. \
+             Pass --force-system to use it anyway, or run 'cargo xtask setup'.",
+            godot,
+            reported,
+            config.godot.version
+        );
+    }
+
+    warn!(
+        "System Godot at {:?} is {} but {} is configured, using it anyway.",
+        godot, reported, config.godot.version
+    );
+    Ok(())
+}
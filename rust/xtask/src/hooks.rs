@@ -0,0 +1,28 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs the `[hooks]` commands of one stage in order, from the workspace root. A hook that fails
+/// fails the whole command.
+pub fn run_hooks(root: &Path, stage: &str, hooks: &[Vec<String>]) -> Result<()> {
+    for hook in hooks {
+        let Some((program, args)) = hook.split_first() else {
+            continue;
+        };
+
+        let mut cmd = Command::new(program);
+        cmd.args(args).current_dir(root).env("RUSTYGODOT_HOOK", stage);
+        if crate::dry_run(format_args!("run the {} hook {:?}", stage, cmd)) {
+            continue;
+        }
+
+        info!("Running {} hook: {}", stage, hook.join(" "));
+        crate::trace_command(&cmd);
+        let status = cmd.status().with_context(|| format!("Failed to run {} hook '{}'", stage, program))?;
+        if !status.success() {
+            anyhow::bail!("{} hook '{}' failed ({})", stage, hook.join(" "), status);
+        }
+    }
+    Ok(())
+}
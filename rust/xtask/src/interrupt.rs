@@ -0,0 +1,151 @@
+use log::{debug, warn};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Exit code of a run stopped with Ctrl-C, the one shells use for SIGINT.
+const INTERRUPTED: i32 = 130;
+
+/// How often `status_with_timeout` checks whether the child is done.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Half-written files and folders to remove if the run is interrupted.
+static TEMP_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+/// Cargo and Godot processes to stop if the run is interrupted.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Installs the Ctrl-C handler. Without one, an interrupted download or extraction leaves its
+/// temporary files behind, and a child process may keep running.
+pub fn install() {
+    if let Err(err) = ctrlc::set_handler(on_interrupt) {
+        debug!("Failed to install the Ctrl-C handler: {}", err);
+    }
+}
+
+fn on_interrupt() {
+    warn!("Interrupted, cleaning up...");
+    for pid in lock(&CHILDREN).drain(..) {
+        kill(pid);
+    }
+    for path in lock(&TEMP_PATHS).drain(..) {
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match removed {
+            Ok(()) => debug!("Removed {:?}", path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => warn!("Failed to remove {:?}: {}", path, err),
+        }
+    }
+    std::process::exit(INTERRUPTED);
+}
+
+/// A temporary file or folder that's removed if the run is interrupted while the guard lives.
+/// Dropping the guard doesn't remove it, the caller renames or removes it once it's done.
+pub struct TempPath(PathBuf);
+
+pub fn temp_path(path: &Path) -> TempPath {
+    lock(&TEMP_PATHS).push(path.to_path_buf());
+    TempPath(path.to_path_buf())
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        lock(&TEMP_PATHS).retain(|path| *path != self.0);
+    }
+}
+
+/// A child process that's stopped if the run is interrupted while the guard lives.
+pub struct TrackedChild(u32);
+
+pub fn track(child: &Child) -> TrackedChild {
+    lock(&CHILDREN).push(child.id());
+    TrackedChild(child.id())
+}
+
+impl Drop for TrackedChild {
+    fn drop(&mut self) {
+        lock(&CHILDREN).retain(|pid| *pid != self.0);
+    }
+}
+
+/// `cmd.status()`, with the child stopped if the run is interrupted.
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    let mut child = cmd.spawn()?;
+    let _tracked = track(&child);
+    child.wait()
+}
+
+/// `cmd.status()`, with the child and everything it started stopped once `timeout` has passed,
+/// which returns no exit status.
+pub fn status_with_timeout(cmd: &mut Command, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    isolate_group(cmd);
+    let mut child = cmd.spawn()?;
+    let _tracked = track(&child);
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill_tree(&mut child);
+            return Ok(None);
+        }
+        std::thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+    }
+}
+
+/// On Unix the child gets its own process group, so `kill_tree` also reaches what it started.
+pub fn isolate_group(cmd: &mut Command) {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(cmd, 0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// Stops a child started after `isolate_group`, and its own children.
+pub fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = Command::new("kill");
+        cmd.arg("-KILL").arg("--").arg(format!("-{}", child.id()));
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/PID", &child.id().to_string(), "/T", "/F"]);
+        cmd
+    };
+    if let Err(err) = cmd.stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null()).status() {
+        debug!("Failed to stop process {}: {}", child.id(), err);
+    }
+    // In case the group couldn't be stopped, the child itself at least.
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Stops a child that ignored the Ctrl-C the terminal sent it too, or never got it.
+fn kill(pid: u32) {
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = Command::new("kill");
+        cmd.arg(pid.to_string());
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/PID", &pid.to_string(), "/T", "/F"]);
+        cmd
+    };
+    if let Err(err) = cmd.stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null()).status() {
+        debug!("Failed to stop process {}: {}", pid, err);
+    }
+}
+
+/// The handler runs on its own thread, so a panic elsewhere must not keep it from cleaning up.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
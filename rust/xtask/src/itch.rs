@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use log::{debug, error, info};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::export_log;
+use crate::manifest::MANIFEST_FILE;
+use crate::platform::Platform;
+
+/// Checks `itch.project` is "user/game", before anything is built for `--publish`.
+pub fn parse_project(project: &str) -> Result<(&str, &str)> {
+    match project.split_once('/') {
+        Some((user, game)) if !user.is_empty() && !game.is_empty() && !game.contains('/') && !project.contains(':') => Ok((user, game)),
+        _ => anyhow::bail!("expected the project as \"user/game\", like in user.itch.io/game"),
+    }
+}
+
+/// The configured `itch.project`, or what to set when there's none.
+pub fn project(config: &Config) -> Result<&str> {
+    let project = config
+        .itch
+        .project
+        .as_deref()
+        .context("--publish needs the itch.io project: set `project = \"user/game\"` under [itch] in xtask.toml")?;
+    parse_project(project).with_context(|| format!("itch.project '{}'", project))?;
+    Ok(project)
+}
+
+/// Pushes every export to its itch.io channel with `butler push`. `exports` are what `package`
+/// shipped: a platform folder, or the archive with `--archive`. Without butler on PATH nothing
+/// is pushed, the exports are there to upload by hand. A failing push doesn't stop the others.
+pub fn publish(config: &Config, exports: &[(Platform, PathBuf)], version: Option<&str>) -> Result<()> {
+    let project = project(config)?;
+    if exports.is_empty() {
+        info!("--publish: nothing was exported, nothing to push");
+        return Ok(());
+    }
+    if !butler_installed() {
+        info!("--publish: butler isn't on PATH, skipping the upload. Install it from https://itch.io/docs/butler/ to push to {}", project);
+        report_skipped(config, project, exports);
+        return Ok(());
+    }
+    if std::env::var_os("BUTLER_API_KEY").is_none() {
+        debug!("BUTLER_API_KEY isn't set, butler uses the credentials of 'butler login'");
+    }
+
+    let mut failed = Vec::new();
+    for (platform, path) in exports {
+        let target = format!("{}:{}", project, config.itch.channel(*platform, config.export.role));
+        let mut cmd = Command::new("butler");
+        cmd.arg("push").arg(path).arg(&target);
+        // A platform folder also has the files xtask writes next to the export.
+        if path.is_dir() {
+            cmd.arg("--ignore").arg(MANIFEST_FILE).arg("--ignore").arg(export_log::LOG_FILE);
+        }
+        if let Some(version) = version {
+            cmd.arg("--userversion").arg(version);
+        }
+        if crate::dry_run(format_args!("run {:?}", cmd)) {
+            continue;
+        }
+        info!("Pushing {:?} to {}...", path, target);
+        crate::trace_command(&cmd);
+        let status = cmd.status().context("Failed to run butler")?;
+        crate::report::event(
+            "publish",
+            json!({ "platform": platform.dir_name(), "target": target, "status": if status.success() { "ok" } else { "failed" }, "exit_code": status.code() }),
+        );
+        if status.success() {
+            info!("Pushed {} to {}", platform.dir_name(), target);
+        } else {
+            error!("butler push to {} failed ({})", target, status);
+            failed.push(platform.dir_name());
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("Publishing failed for {} of {} platforms: {}", failed.len(), exports.len(), failed.join(", "));
+    }
+    Ok(())
+}
+
+fn butler_installed() -> bool {
+    let mut cmd = Command::new("butler");
+    cmd.arg("-V").stdout(Stdio::null()).stderr(Stdio::null());
+    crate::trace_command(&cmd);
+    cmd.status().is_ok_and(|status| status.success())
+}
+
+fn report_skipped(config: &Config, project: &str, exports: &[(Platform, PathBuf)]) {
+    for (platform, path) in exports {
+        let target = format!("{}:{}", project, config.itch.channel(*platform, config.export.role));
+        crate::report::event("publish", json!({ "platform": platform.dir_name(), "target": target, "path": path, "status": "skipped" }));
+    }
+}
+
+/// What's pushed for an export: the archive as it is, or the folder the export is in.
+pub fn upload_path(shipped: &Path, archived: bool) -> PathBuf {
+    match shipped.parent() {
+        Some(dir) if !archived => dir.to_path_buf(),
+        _ => shipped.to_path_buf(),
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::config::GodotConfig;
+
+pub const LOCK_FILE: &str = "xtask.lock";
+
+/// The Godot install `setup` last verified, written to `xtask.lock` at the workspace root.
+/// Committed, it makes everyone on the team (and CI) install the exact same engine.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Lock {
+    pub godot: LockedGodot,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct LockedGodot {
+    /// Release tag, e.g. "4.6-stable".
+    pub version: String,
+    pub mono: bool,
+    /// SHA-512 of the editor archive.
+    pub editor_sha512: String,
+    /// Folder name of the export templates, e.g. "4.6.0-stable".
+    pub templates_version: String,
+    /// SHA-512 of the .tpz archive.
+    pub templates_sha512: String,
+}
+
+impl Lock {
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {:?}, delete it and run 'cargo xtask setup' again", path))
+    }
+
+    pub fn write(&self, root: &Path) -> Result<()> {
+        let path = root.join(LOCK_FILE);
+        if crate::dry_run(format_args!("write {:?}", path)) {
+            return Ok(());
+        }
+        let content = format!(
+            "# Written by `cargo xtask setup`, do not edit. Commit it so everyone installs the same Godot.\n{}",
+            toml::to_string(self)?
+        );
+        fs::write(&path, content)?;
+        info!("Locked Godot {} in {}", self.godot.version, LOCK_FILE);
+        Ok(())
+    }
+}
+
+/// Compares the configured Godot with the locked one. A difference is a warning, or an error
+/// with `frozen`, which also requires the lock to exist.
+pub fn check(root: &Path, godot: &GodotConfig, frozen: bool) -> Result<Option<Lock>> {
+    let Some(lock) = Lock::load(root)? else {
+        if frozen {
+            anyhow::bail!("--frozen: there is no {}, run 'cargo xtask setup' without --frozen to create it", LOCK_FILE);
+        }
+        return Ok(None);
+    };
+
+    let locked = &lock.godot;
+    if locked.version != godot.version || locked.mono != godot.mono {
+        let mono = |mono: bool| if mono { " (.NET)" } else { "" };
+        let message = format!(
+            "{} locks Godot {}{}, but {}{} is configured",
+            LOCK_FILE,
+            locked.version,
+            mono(locked.mono),
+            godot.version,
+            mono(godot.mono)
+        );
+        if frozen {
+            anyhow::bail!("--frozen: {}", message);
+        }
+        warn!("{}. 'cargo xtask setup' updates the lock.", message);
+    }
+    Ok(Some(lock))
+}
+
+/// Checks a fresh install against the lock and records it. With `frozen` the checksums have
+/// to match the locked ones and the lock is never rewritten.
+pub fn update(root: &Path, previous: Option<&Lock>, installed: Lock, frozen: bool) -> Result<()> {
+    if let Some(previous) = previous.filter(|previous| previous.godot.version == installed.godot.version) {
+        for (what, locked, actual) in [
+            ("editor", &previous.godot.editor_sha512, &installed.godot.editor_sha512),
+            ("export templates", &previous.godot.templates_sha512, &installed.godot.templates_sha512),
+        ] {
+            if locked != actual {
+                let message = format!(
+                    "The {} archive of Godot {} has SHA-512 {}, but {} locks {}",
+                    what, installed.godot.version, actual, LOCK_FILE, locked
+                );
+                if frozen {
+                    anyhow::bail!("--frozen: {}", message);
+                }
+                warn!("{}. The mirror may have republished it; updating the lock.", message);
+            }
+        }
+    }
+
+    if previous != Some(&installed) && !frozen {
+        installed.write(root)?;
+    }
+    Ok(())
+}
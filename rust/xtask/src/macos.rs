@@ -0,0 +1,314 @@
+use anyhow::{Context, Result};
+use log::{info, trace, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::MacosConfig;
+use crate::platform::{Arch, Platform};
+
+/// Warns when the library is built for another architecture than the Mac's own. A native
+/// Godot only loads the `macos/<arch>/` library of the CPU it runs on, and without it the
+/// extension silently doesn't load.
+pub fn check_native_arch(arch: Arch) {
+    if Platform::host() != Platform::Macos {
+        return;
+    }
+    // Also reports Apple Silicon from a process running under Rosetta, unlike `uname -m`.
+    let apple_silicon = Command::new("sysctl")
+        .arg("-n")
+        .arg("hw.optional.arm64")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "1");
+    let native = if apple_silicon { Arch::Arm64 } else { Arch::X86_64 };
+    if arch != native {
+        let triple = if native == Arch::Arm64 { "aarch64-apple-darwin" } else { "x86_64-apple-darwin" };
+        warn!(
+            "Building the macOS library for {}, but this Mac is {}: a native Godot looks for macos/{}/ and won't load the extension. \
+             Install the {} Rust toolchain, or build with --target {} or --universal.",
+            arch.tag(),
+            native.tag(),
+            native.tag(),
+            triple,
+            triple
+        );
+    }
+}
+
+/// Signs the app inside an exported macOS zip with `identity` and zips it up again, then
+/// optionally notarizes it and staples the ticket to the app.
+///
+/// Godot only exports the zip, so the app is unpacked and repacked with `ditto`, which keeps
+/// the symlinks and extended attributes the signature depends on.
+pub fn sign_export(export_zip: &Path, identity: &str, notarize: Option<&MacosConfig>) -> Result<()> {
+    let and_notarize = if notarize.is_some() { " and notarize it" } else { "" };
+    if crate::dry_run(format_args!("sign the app in {:?} as \"{}\"{}", export_zip, identity, and_notarize)) {
+        return Ok(());
+    }
+
+    let work_dir = export_zip.with_extension("signing");
+    if work_dir.exists() {
+        fs::remove_dir_all(&work_dir)?;
+    }
+    run(Command::new("ditto").arg("-x").arg("-k").arg(export_zip).arg(&work_dir), "ditto")?;
+
+    let app = fs::read_dir(&work_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().is_some_and(|ext| ext == "app"))
+        .with_context(|| format!("No .app bundle found in {:?}", export_zip))?;
+
+    info!("Signing {:?} as \"{}\"...", app.file_name().unwrap_or_default(), identity);
+    run(
+        Command::new("codesign")
+            .arg("--deep")
+            .arg("--force")
+            .arg("--options")
+            .arg("runtime")
+            .arg("--timestamp")
+            .arg("--sign")
+            .arg(identity)
+            .arg(&app),
+        "codesign",
+    )?;
+
+    let mut verify = Command::new("codesign");
+    verify.arg("--verify").arg("--deep").arg("--strict").arg("--verbose=2").arg(&app);
+    crate::trace_command(&verify);
+    let output = verify.output().context("Failed to run codesign --verify")?;
+    let report = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        anyhow::bail!("codesign --verify rejected {:?}:\n{}", app, report.trim());
+    }
+    info!("codesign --verify: {}", report.trim());
+
+    zip_app(&app, export_zip)?;
+
+    // Apple notarizes the zip, but the ticket can only be stapled to the app itself.
+    if let Some(config) = notarize {
+        notarize_zip(export_zip, config)?;
+        run(Command::new("xcrun").arg("stapler").arg("staple").arg(&app), "stapler")?;
+        zip_app(&app, export_zip)?;
+        info!("Notarization ticket stapled to {:?}", app.file_name().unwrap_or_default());
+    }
+
+    if let Err(err) = fs::remove_dir_all(&work_dir) {
+        warn!("Failed to clean up {:?}: {}", work_dir, err);
+    }
+
+    info!("Signed app repacked into {:?}", export_zip);
+    Ok(())
+}
+
+/// Puts `files` into the Contents/Frameworks folder of the app in an exported macOS zip, where
+/// the app's @rpath finds libraries.
+pub fn add_frameworks(export_zip: &Path, files: &[PathBuf]) -> Result<()> {
+    let work_dir = export_zip.with_extension("frameworks");
+    if work_dir.exists() {
+        fs::remove_dir_all(&work_dir)?;
+    }
+    run(Command::new("ditto").arg("-x").arg("-k").arg(export_zip).arg(&work_dir), "ditto")?;
+
+    let app = fs::read_dir(&work_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().is_some_and(|ext| ext == "app"))
+        .with_context(|| format!("No .app bundle found in {:?}", export_zip))?;
+    let frameworks = app.join("Contents/Frameworks");
+    fs::create_dir_all(&frameworks)?;
+    for file in files {
+        let name = file.file_name().with_context(|| format!("{:?} is not a file", file))?;
+        fs::copy(file, frameworks.join(name)).with_context(|| format!("Failed to copy {:?} into the app", file))?;
+        info!("Added {:?} to {:?}", name, frameworks);
+    }
+
+    zip_app(&app, export_zip)?;
+    if let Err(err) = fs::remove_dir_all(&work_dir) {
+        warn!("Failed to clean up {:?}: {}", work_dir, err);
+    }
+    Ok(())
+}
+
+fn zip_app(app: &Path, export_zip: &Path) -> Result<()> {
+    if export_zip.exists() {
+        fs::remove_file(export_zip)?;
+    }
+    run(
+        Command::new("ditto").arg("-c").arg("-k").arg("--keepParent").arg(app).arg(export_zip),
+        "ditto",
+    )
+}
+
+/// Submits the zip to Apple's notary service and waits for the verdict.
+fn notarize_zip(export_zip: &Path, config: &MacosConfig) -> Result<()> {
+    let credentials = notary_credentials(config)?;
+
+    info!("Submitting {:?} for notarization (this can take a few minutes)...", export_zip);
+    let mut submit = Command::new("xcrun");
+    submit
+        .arg("notarytool")
+        .arg("submit")
+        .arg(export_zip)
+        .args(&credentials)
+        .arg("--wait")
+        .arg("--output-format")
+        .arg("json");
+    // Not traced like other commands, the arguments may contain the password.
+    trace!("Running xcrun notarytool submit {:?} --wait", export_zip);
+    let output = submit.output().context("Failed to run xcrun notarytool")?;
+
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).with_context(|| {
+        format!("notarytool submit failed:\n{}", String::from_utf8_lossy(&output.stderr).trim())
+    })?;
+    let id = result["id"].as_str().unwrap_or_default();
+    let status = result["status"].as_str().unwrap_or("unknown");
+
+    if status != "Accepted" {
+        // The log lists every file Apple rejected and why.
+        let mut log = Command::new("xcrun");
+        log.arg("notarytool").arg("log").arg(id).args(&credentials);
+        trace!("Running xcrun notarytool log {}", id);
+        let details = log
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_default();
+        anyhow::bail!(
+            "Notarization of {:?} finished with status '{}' (submission {}).\n{}",
+            export_zip,
+            status,
+            id,
+            details
+        );
+    }
+
+    info!("Notarization accepted (submission {})", id);
+    Ok(())
+}
+
+/// Authentication arguments of notarytool: a keychain profile, or an Apple ID with its team id and
+/// an app-specific password. The password is only read from APPLE_APP_PASSWORD, never from xtask.toml.
+fn notary_credentials(config: &MacosConfig) -> Result<Vec<String>> {
+    if let Some(profile) = &config.keychain_profile {
+        return Ok(vec!["--keychain-profile".to_string(), profile.clone()]);
+    }
+
+    let apple_id = std::env::var("APPLE_ID").ok().or(config.apple_id.clone());
+    let team_id = std::env::var("APPLE_TEAM_ID").ok().or(config.team_id.clone());
+    let password = std::env::var("APPLE_APP_PASSWORD").ok();
+    match (apple_id, team_id, password) {
+        (Some(apple_id), Some(team_id), Some(password)) => Ok(vec![
+            "--apple-id".to_string(),
+            apple_id,
+            "--team-id".to_string(),
+            team_id,
+            "--password".to_string(),
+            password,
+        ]),
+        _ => anyhow::bail!(
+            "Notarization needs `keychain_profile` under [macos], or APPLE_ID, APPLE_TEAM_ID and APPLE_APP_PASSWORD."
+        ),
+    }
+}
+
+/// Fills in what the generated macOS preset needs: the version from project.godot when
+/// `version` isn't set, and an .icns made from a PNG `icon`.
+pub fn prepare_bundle(game_dir: &Path, macos: &mut MacosConfig) -> Result<()> {
+    if macos.version.is_none() {
+        macos.version = crate::godot::project_setting(game_dir, "config/version");
+    }
+    if let Some(icon) = &macos.icon {
+        macos.icon = Some(icns_icon(game_dir, icon)?);
+    }
+    Ok(())
+}
+
+/// The .icns next to a PNG icon, converted with sips and iconutil if there isn't one yet.
+/// Elsewhere than on macOS the PNG is kept, which Godot converts itself at export time.
+fn icns_icon(game_dir: &Path, icon: &str) -> Result<String> {
+    let Some(stem) = icon.strip_suffix(".png") else {
+        return Ok(icon.to_string());
+    };
+    let icns_res = format!("{stem}.icns");
+    let png = game_dir.join(icon.trim_start_matches("res://"));
+    let icns = png.with_extension("icns");
+    if icns.exists() {
+        return Ok(icns_res);
+    }
+    if !png.exists() {
+        anyhow::bail!("The macOS icon {:?} doesn't exist", png);
+    }
+    if Platform::host() != Platform::Macos {
+        info!("Converting the icon to .icns needs macOS, the export uses {} as is", icon);
+        return Ok(icon.to_string());
+    }
+    if crate::dry_run(format_args!("convert {:?} to {:?}", png, icns)) {
+        return Ok(icns_res);
+    }
+
+    // iconutil wants every size in an .iconset folder, which sips scales the PNG to.
+    let iconset = std::env::temp_dir().join("rustygodot-icon.iconset");
+    if iconset.exists() {
+        fs::remove_dir_all(&iconset)?;
+    }
+    fs::create_dir_all(&iconset)?;
+    for size in [16, 32, 128, 256, 512] {
+        for (scale, suffix) in [(1, ""), (2, "@2x")] {
+            let pixels = (size * scale).to_string();
+            let mut sips = Command::new("sips");
+            sips.arg("-z")
+                .arg(&pixels)
+                .arg(&pixels)
+                .arg(&png)
+                .arg("--out")
+                .arg(iconset.join(format!("icon_{size}x{size}{suffix}.png")))
+                .stdout(std::process::Stdio::null());
+            run(&mut sips, "sips")?;
+        }
+    }
+    let mut iconutil = Command::new("iconutil");
+    iconutil.arg("-c").arg("icns").arg(&iconset).arg("-o").arg(&icns);
+    run(&mut iconutil, "iconutil")?;
+    fs::remove_dir_all(&iconset)?;
+    info!("Converted {:?} to {:?}", png, icns);
+    Ok(icns_res)
+}
+
+/// Warns when the exported app's Info.plist doesn't have the configured bundle identifier,
+/// which happens when an existing preset sets another one.
+pub fn check_bundle_identifier(export_zip: &Path, macos: &MacosConfig) -> Result<()> {
+    let Some(expected) = &macos.bundle_identifier else {
+        return Ok(());
+    };
+    let mut archive = zip::ZipArchive::new(fs::File::open(export_zip)?)?;
+    let Some(name) = archive.file_names().find(|name| name.ends_with(".app/Contents/Info.plist")).map(str::to_string) else {
+        warn!("No Info.plist found in {:?}, can't check the bundle identifier", export_zip);
+        return Ok(());
+    };
+    let mut plist = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name(&name)?, &mut plist)?;
+
+    match plist_string(&plist, "CFBundleIdentifier") {
+        Some(actual) if actual == *expected => info!("Bundle identifier: {}", actual),
+        Some(actual) => warn!(
+            "The exported app's bundle identifier is {}, not {}. Change it in the existing macOS preset (Application > Bundle Identifier).",
+            actual, expected
+        ),
+        None => warn!("The exported app's Info.plist has no CFBundleIdentifier"),
+    }
+    Ok(())
+}
+
+/// The `<string>` following `<key>{key}</key>` in an XML property list.
+fn plist_string(plist: &str, key: &str) -> Option<String> {
+    let after_key = &plist[plist.find(&format!("<key>{key}</key>"))?..];
+    let start = after_key.find("<string>")? + "<string>".len();
+    let end = after_key[start..].find("</string>")?;
+    Some(after_key[start..start + end].trim().to_string())
+}
+
+fn run(cmd: &mut Command, name: &str) -> Result<()> {
+    crate::trace_command(cmd);
+    let status = cmd.status().with_context(|| format!("Failed to run {}", name))?;
+    if !status.success() {
+        anyhow::bail!("{} failed ({})", name, status);
+    }
+    Ok(())
+}
@@ -1,418 +1,3946 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::parser::{ArgMatches, ValueSource};
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use log::{debug, error, info, trace, warn, LevelFilter};
+use serde_json::json;
 use std::fs;
-use std::io::{Cursor, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::{Command};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
+mod archive;
+mod assets;
+mod binary_arch;
+mod build_info;
+mod compat;
+mod config;
+mod config_report;
+mod disk;
+mod download;
+mod examples;
+mod export_log;
+mod extract;
+mod gdextension;
+mod godot;
+mod hooks;
+mod interrupt;
+mod itch;
+mod lock;
+mod macos;
+mod manifest;
+mod metadata;
+mod platform;
+mod report;
+mod scaffold;
+mod self_update;
+mod smoke_test;
+mod startup_profile;
+mod targets;
+mod watch;
+mod windows;
 
-const GODOT_VERSION: &str = "4.6-stable";
-const GODOT_VERSION_FULL: &str = "4.6.0-stable";
+use config::{Config, ExportConfig, ExtensionConfig, GodotConfig, HooksConfig, Role};
+use config_report::Origin;
+use gdextension::generate_gdextension_file;
+use metadata::{ExtensionCrate, Metadata};
+use platform::{Arch, Platform};
+use report::OutputFormat;
 
-const BASE_URL: &str = "https://github.com/godotengine/godot/releases/download";
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
 
 #[derive(Parser)]
 #[command(name = "xtask")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Use a Godot found on PATH instead of the one installed by 'setup'
+    #[arg(long, global = true)]
+    use_system: bool,
+    /// Use the system Godot even if its version doesn't match the configured one
+    #[arg(long, global = true)]
+    force_system: bool,
+    /// Use the .NET (Mono) build of Godot, for projects mixing C# and Rust
+    #[arg(long, global = true)]
+    mono: bool,
+    /// Print more details, -vv also prints every command line that is run
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print the commands, downloads and file writes that would happen, without doing them
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Stop Godot (and what it started) if it runs longer than this many seconds, e.g. a run,
+    /// test, import or export stuck on a crash dialog
+    #[arg(long, global = true, value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+    /// Godot release tag to use instead of `godot.version`, e.g. 4.5-stable
+    #[arg(long, global = true)]
+    godot_version: Option<String>,
+    /// Folder Godot is installed into and run from (defaults to `godot.install_dir`, i.e. .godot_bin)
+    #[arg(long, global = true, env = "RUSTYGODOT_GODOT_DIR")]
+    godot_dir: Option<PathBuf>,
+    /// Folder of the Godot project, relative to the workspace root (defaults to `project.dir`, i.e. game)
+    #[arg(long, global = true, env = "RUSTYGODOT_PROJECT_DIR")]
+    project_dir: Option<PathBuf>,
+    /// Name of the game in a generated project.godot, instead of `project.name` (an existing project is never changed)
+    #[arg(long, global = true)]
+    project_name: Option<String>,
+    /// Never use the network: only cached downloads, and cargo runs with --offline
+    #[arg(long, global = true, env = "RUSTYGODOT_OFFLINE", value_parser = clap::builder::FalseyValueParser::new())]
+    offline: bool,
+    /// Output format: human readable messages, or newline-delimited JSON events for CI
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+    /// Skip the `[hooks]` commands from xtask.toml
+    #[arg(long, global = true)]
+    no_hooks: bool,
+    /// Fail instead of warning when the Godot install doesn't match xtask.lock, and never update it
+    #[arg(long, global = true)]
+    frozen: bool,
+    /// Pass --verbose to Godot when running, editing, importing and exporting, independently of -v
+    #[arg(long, global = true)]
+    godot_verbose: bool,
+    /// With editor, run and package: write the configured engine version into the
+    /// `config/features` of project.godot when it lists another one
+    #[arg(long, global = true)]
+    update_project_features: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Scaffold a new Godot + Rust project in a new directory
+    New {
+        name: String,
+        /// Scaffold even if the directory is not empty
+        #[arg(long)]
+        force: bool,
+    },
+    /// Scaffold a new Godot + Rust project in the current directory
+    Init {
+        /// Scaffold even if the directory is not empty
+        #[arg(long)]
+        force: bool,
+    },
     /// Download and setup Godot Engine and Templates
-    Setup,
+    Setup {
+        #[command(flatten)]
+        setup: SetupArgs,
+    },
+    /// Add the files xtask generates to the workspace .gitignore (safe to run again)
+    InitGitignore,
+    /// Print the settings as they will be used, where each one comes from, and check them
+    Config,
+    /// List the Godot versions installed by 'setup'
+    List,
+    /// Show where the export templates are, which versions are installed and their files
+    Templates,
+    /// Show which platforms can be built and exported on this machine, and what the others miss
+    ListTargets,
+    /// Print the versions of xtask, Godot and gdext and the host, to paste into a bug report
+    Version,
+    /// List the example scenes 'run --example' can run
+    Examples,
+    /// Remove the editor and export templates installed by 'setup'
+    Uninstall {
+        /// Version to remove (defaults to the configured one)
+        #[arg(long)]
+        version: Option<String>,
+        /// Only remove the export templates
+        #[arg(long)]
+        templates: bool,
+        /// Only remove the editor
+        #[arg(long)]
+        editor: bool,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
     /// Build Rust crates and copy artifacts to game/bin
     Build {
         #[arg(long)]
         release: bool,
+        #[command(flatten)]
+        build: BuildArgs,
     },
     /// Build and open the Godot Editor
-    Editor,
+    Editor {
+        #[command(flatten)]
+        build: BuildArgs,
+        /// Scene to open in the editor, e.g. res://levels/boss.tscn
+        #[arg(long)]
+        scene: Option<String>,
+        /// Don't build, use the libraries already in game/bin
+        #[arg(long)]
+        no_build: bool,
+        /// Start the editor in the background and return to the shell right away
+        #[arg(long)]
+        detach: bool,
+        /// Arguments passed on to the project, after `--`
+        #[arg(last = true)]
+        game_args: Vec<String>,
+    },
+    /// Open the Godot Editor and rebuild the extension whenever the Rust sources change
+    Watch {
+        #[command(flatten)]
+        build: BuildArgs,
+        /// Also import the project's assets headlessly whenever they change
+        #[arg(long)]
+        assets: bool,
+    },
     /// Build and run the game
-    Run,
+    Run {
+        #[command(flatten)]
+        build: BuildArgs,
+        /// Scene to run instead of the main scene, e.g. res://levels/boss.tscn
+        #[arg(long)]
+        scene: Option<String>,
+        /// Example scene to run, by name (see 'examples')
+        #[arg(long, conflicts_with = "scene")]
+        example: Option<String>,
+        /// Don't build, use the libraries already in game/bin
+        #[arg(long)]
+        no_build: bool,
+        /// Start the game in the background and return to the shell right away
+        #[arg(long)]
+        detach: bool,
+        /// Start the game with --verbose for one frame and report how long the extension and
+        /// the main scene took to load. The timed log goes to target/startup-profile.log
+        #[arg(long, conflicts_with = "detach")]
+        profile_startup: bool,
+        #[command(flatten)]
+        launch: LaunchArgs,
+        /// Arguments passed on to the game, after `--` (read them with OS.get_cmdline_user_args())
+        #[arg(last = true)]
+        game_args: Vec<String>,
+    },
+    /// Build and run the project's tests headlessly, exiting with the runner's exit code
+    Test {
+        /// Test runner script, run with `-s` (defaults to `test.script` in xtask.toml)
+        #[arg(long, conflicts_with = "scene")]
+        script: Option<String>,
+        /// Test runner scene to run instead of a script
+        #[arg(long)]
+        scene: Option<String>,
+        /// Don't import the assets first, when the import cache in .godot/ is known to be current
+        #[arg(long)]
+        no_import: bool,
+        #[command(flatten)]
+        build: BuildArgs,
+    },
     /// Build and Package the game for distribution
-    Package
+    Package {
+        #[command(flatten)]
+        package: PackageArgs,
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+    /// Run the configured Godot on the project with the arguments after `--`, e.g.
+    /// `godot -- --headless --check-only -s res://main.gd`, exiting with its exit code
+    Godot {
+        /// Arguments passed on to Godot, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Replace this xtask binary with the latest release (prebuilt binaries only, not `cargo xtask`)
+    SelfUpdate {
+        /// Release feed to check: a JSON file with the latest version and a binary per platform
+        #[arg(long, env = "RUSTYGODOT_UPDATE_FEED", default_value = self_update::DEFAULT_FEED)]
+        feed: String,
+        /// Only print the current and the latest version
+        #[arg(long)]
+        check: bool,
+    },
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions {
+        shell: clap_complete::Shell,
+    },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let root = std::env::current_dir()?;
-
-    match cli.command {
-        Commands::Setup => setup_godot(&root)?,
-        Commands::Build { release } => build_and_install(&root, release)?,
-        Commands::Editor => {
-            build_and_install(&root, false)?;
-            run_godot(&root, true)?;
-        }
-        Commands::Run => {
-            build_and_install(&root, false)?;
-            run_godot(&root, false)?;
-        },
-        Commands::Package => {
-            build_and_install(&root, true)?;
-            ensure_export_presets(&root.join("game"))?;
-            package_game(&root)?;
+impl Commands {
+    /// The build options of the commands that compile the crates.
+    fn build_args_mut(&mut self) -> Option<&mut BuildArgs> {
+        match self {
+            Commands::Build { build, .. }
+            | Commands::Editor { build, .. }
+            | Commands::Watch { build, .. }
+            | Commands::Run { build, .. }
+            | Commands::Test { build, .. }
+            | Commands::Package { build, .. } => Some(build),
+            _ => None,
         }
     }
+}
 
-    Ok(())
+/// Options of the setup command.
+#[derive(Args, Clone, Default)]
+struct SetupArgs {
+    /// Download the editor and templates even if they are in the download cache
+    #[arg(long)]
+    no_cache: bool,
+    /// Delete the download cache and exit
+    #[arg(long)]
+    clear_cache: bool,
+    /// Only install the editor, not the export templates
+    #[arg(long)]
+    no_templates: bool,
+    /// Delete and reinstall the editor and the export templates even if they look installed,
+    /// to repair a broken install
+    #[arg(long, conflicts_with = "clear_cache")]
+    force: bool,
+    /// Turn the installed templates of a close version, e.g. 4.6.0 for 4.6.1, into the new
+    /// ones by only replacing the files that changed, instead of writing all of them
+    #[arg(long, conflicts_with_all = ["clear_cache", "no_templates"])]
+    update_templates: bool,
+    /// Ask for the version, the install folder and whether to install the templates (only in a terminal)
+    #[arg(long, conflicts_with = "clear_cache")]
+    interactive: bool,
 }
 
-fn get_os_info() -> (&'static str, &'static str) {
-    if cfg!(target_os = "windows") {
-        ("win64.exe.zip", "Godot_v4.6-stable_win64.exe")
-    } else if cfg!(target_os = "macos") {
-        ("macos.universal.zip", "Godot.app/Contents/MacOS/Godot")
-    } else {
-        ("linux.x86_64.zip", "Godot_v4.6-stable_linux.x86_64")
+/// Window, driver, language and debugger options of the run command, passed on to Godot.
+#[derive(Args, Clone, Default)]
+struct LaunchArgs {
+    /// Window size, e.g. 1280x720 (Godot's --resolution)
+    #[arg(long, value_parser = parse_resolution)]
+    resolution: Option<String>,
+    /// Run in a window, even if the project starts fullscreen
+    #[arg(long, conflicts_with_all = ["fullscreen", "maximized"])]
+    windowed: bool,
+    /// Run fullscreen
+    #[arg(long, conflicts_with = "maximized")]
+    fullscreen: bool,
+    /// Run in a maximized window
+    #[arg(long)]
+    maximized: bool,
+    /// Run without a window or sound, like `package` and `test` do, e.g. for a quick launch
+    /// test of the game logic in CI (Godot's --headless, with the Dummy audio driver)
+    #[arg(long, conflicts_with_all = ["resolution", "windowed", "fullscreen", "maximized"])]
+    headless: bool,
+    /// Audio driver to use, e.g. Dummy for none (Godot's --audio-driver)
+    #[arg(long, value_parser = AUDIO_DRIVERS)]
+    audio_driver: Option<String>,
+    /// Display driver to use, e.g. wayland instead of x11 (Godot's --display-driver)
+    #[arg(long, value_parser = DISPLAY_DRIVERS, conflicts_with = "headless")]
+    display_driver: Option<String>,
+    /// Start in this locale instead of the system's, e.g. fr or pt_BR (Godot's --language)
+    #[arg(long, value_parser = parse_locale)]
+    locale: Option<String>,
+    /// Connect to the debugger of an editor at host:port, or a port on this machine (Godot's --remote-debug)
+    #[arg(long, value_parser = parse_remote_debug)]
+    remote_debug: Option<String>,
+}
+
+/// Audio drivers of the official Godot 4 builds, on every platform.
+const AUDIO_DRIVERS: [&str; 8] = ["Dummy", "PulseAudio", "ALSA", "WASAPI", "XAudio2", "CoreAudio", "OpenSL", "AudioWorklet"];
+
+/// Display drivers of the official Godot 4 builds, on every platform.
+const DISPLAY_DRIVERS: [&str; 7] = ["headless", "x11", "wayland", "windows", "macos", "android", "web"];
+
+impl LaunchArgs {
+    fn apply(&self, cmd: &mut Command) {
+        // The same flags the import and exports run with.
+        if self.headless {
+            cmd.arg("--headless").arg("--display-driver").arg("headless");
+        }
+        let audio_driver = self.audio_driver.as_deref().or(self.headless.then_some("Dummy"));
+        if let Some(driver) = audio_driver {
+            cmd.arg("--audio-driver").arg(driver);
+        }
+        if let Some(driver) = &self.display_driver {
+            cmd.arg("--display-driver").arg(driver);
+        }
+        if let Some(resolution) = &self.resolution {
+            cmd.arg("--resolution").arg(resolution);
+        }
+        for (set, flag) in [(self.windowed, "--windowed"), (self.fullscreen, "--fullscreen"), (self.maximized, "--maximized")] {
+            if set {
+                cmd.arg(flag);
+            }
+        }
+        if let Some(locale) = &self.locale {
+            cmd.arg("--language").arg(locale);
+        }
+        if let Some(address) = &self.remote_debug {
+            cmd.arg("--remote-debug").arg(address);
+        }
     }
 }
 
-fn setup_godot(root: &Path) -> Result<()> {
-    let (zip_suffix, bin_relative_path) = get_os_info();
-    let bin_dir = root.join(".godot_bin");
-    
-    if !bin_dir.exists() {
-        fs::create_dir(&bin_dir)?;
+/// `<platform>=<path>` or a plain path, for `--custom-template`.
+fn parse_custom_template(value: &str) -> Result<(Option<Platform>, PathBuf), String> {
+    if let Some((name, path)) = value.split_once('=') {
+        if let Ok(platform) = Platform::from_str(name, true) {
+            if path.is_empty() {
+                return Err(format!("no template path after '{}='", name));
+            }
+            return Ok((Some(platform), PathBuf::from(path)));
+        }
     }
+    Ok((None, PathBuf::from(value)))
+}
 
-    // Download Editor
-    let version_tag = GODOT_VERSION;
-    let url = format!("{BASE_URL}/{version_tag}/Godot_v{version_tag}_{zip_suffix}");
-    
-    println!("Downloading Godot from: {}", url);
-    let client = reqwest::blocking::Client::builder()
-        .timeout(None) // Disable timeout completely for large files
-        .build()?;
-
-    let response = client.get(&url).send()?.bytes()?;
-    
-    println!("Extracting...");
-    zip::ZipArchive::new(Cursor::new(response))?.extract(&bin_dir)?;
-
-    let binary_path = bin_dir.join(bin_relative_path);
-    if !binary_path.exists() {
-        anyhow::bail!("Extracted binary not found at {:?}", binary_path);
+/// `KEY=VALUE` with a key a shell could set too. The value may be empty or contain `=`.
+fn parse_env(entry: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = entry.split_once('=') else {
+        return Err("expected KEY=VALUE".to_string());
+    };
+    let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return Err(format!("'{}' is not a variable name (letters, digits and _, not starting with a digit)", key));
     }
+    Ok((key.to_string(), value.to_string()))
+}
 
-    // Fix Permissions (Linux & Mac)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
+fn parse_resolution(resolution: &str) -> Result<String, String> {
+    let size = resolution
+        .split_once(['x', 'X'])
+        .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)));
+    match size {
+        Some((width, height)) if width > 0 && height > 0 => Ok(format!("{width}x{height}")),
+        _ => Err("expected <width>x<height>, e.g. 1280x720".to_string()),
+    }
+}
 
-        let mut perms = fs::metadata(&binary_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&binary_path, perms)?;
-        println!("Fixed permissions for: {:?}", binary_path);
+/// A locale code the way Godot writes them: a language, optionally followed by a script and a
+/// country, e.g. "fr", "pt_BR" or "zh_Hans_CN". A dash works as separator too.
+fn parse_locale(locale: &str) -> Result<String, String> {
+    let parts: Vec<&str> = locale.split(['_', '-']).collect();
+    let language = parts[0];
+    let (script, country) = match &parts[1..] {
+        [] => (None, None),
+        [part] if part.len() == 4 => (Some(*part), None),
+        [part] => (None, Some(*part)),
+        [script, country] => (Some(*script), Some(*country)),
+        _ => return Err("expected <language>[_<Script>][_<COUNTRY>], e.g. fr or pt_BR".to_string()),
+    };
+    let valid_language = (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_lowercase());
+    let valid_script = script.is_none_or(|script| {
+        let mut chars = script.chars();
+        script.len() == 4 && chars.next().is_some_and(|c| c.is_ascii_uppercase()) && chars.all(|c| c.is_ascii_lowercase())
+    });
+    let valid_country = country.is_none_or(|country| {
+        (country.len() == 2 && country.chars().all(|c| c.is_ascii_uppercase())) || (country.len() == 3 && country.chars().all(|c| c.is_ascii_digit()))
+    });
+    if valid_language && valid_script && valid_country {
+        Ok(parts.join("_"))
+    } else {
+        Err("expected <language>[_<Script>][_<COUNTRY>], e.g. fr or pt_BR".to_string())
+    }
+}
 
-        // MAC SPECIFIC: Remove the "Quarantine" attribute
-        // macOS blocks downloaded binaries by default (Gatekeeper).
-        #[cfg(target_os = "macos")]
-        {
-            let _ = Command::new("xattr")
-                .arg("-d")
-                .arg("com.apple.quarantine")
-                .arg(&bin_dir.join("Godot.app"))
-                .stdout(std::process::Stdio::null())
-                .stderr(std::process::Stdio::null())
-                .status();
-        }
+/// A debugger address as Godot's --remote-debug takes it, from "host:port", "tcp://host:port"
+/// or just a port, which means this machine.
+fn parse_remote_debug(address: &str) -> Result<String, String> {
+    let address = address.strip_prefix("tcp://").unwrap_or(address);
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port),
+        None => ("127.0.0.1", address),
+    };
+    let valid_host = !host.is_empty()
+        && (host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+            || (host.starts_with('[') && host.ends_with(']') && host.len() > 2));
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 && valid_host => Ok(format!("tcp://{host}:{port}")),
+        _ => Err("expected host:port or a port, e.g. 192.168.1.20:6007 or 6007".to_string()),
     }
+}
 
-    println!("Godot Setup Complete at {:?}", bin_dir);
+/// Options of the package command.
+#[derive(Args, Clone, Default)]
+struct PackageArgs {
+    /// Platform to export (repeatable, defaults to the host platform)
+    #[arg(long = "platform", value_enum)]
+    platforms: Vec<Platform>,
+    /// Export with the debug template and a debug Rust build, into builds/<platform>-debug/
+    #[arg(long)]
+    debug: bool,
+    /// Only export the resource pack (builds/<platform>/<name>.pck), e.g. for patches
+    #[arg(long)]
+    pck_only: bool,
+    /// macOS only: code signing identity for the exported app (defaults to `macos.sign_identity`)
+    #[arg(long)]
+    sign: Option<String>,
+    /// macOS only: notarize the signed app with notarytool and staple the ticket
+    #[arg(long)]
+    notarize: bool,
+    /// Folder the exports are written to, instead of `export.builds_dir` (builds/ by default)
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// Custom feature tag for the generated presets, e.g. demo (repeatable, defaults to `export.custom_features`)
+    #[arg(long = "feature")]
+    feature_tags: Vec<String>,
+    /// Extra files for the generated presets to export, comma separated globs like "*.json" (defaults to `export.include_filter`)
+    #[arg(long, value_delimiter = ',')]
+    include: Vec<String>,
+    /// Files the generated presets leave out, comma separated globs like "*.blend,*.psd" (defaults to `export.exclude_filter`)
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+    /// Don't build, export the libraries already in game/bin
+    #[arg(long)]
+    no_build: bool,
+    /// Start the exported game once it's exported, to check the build that ships (host platform only)
+    #[arg(long, conflicts_with = "pck_only")]
+    run_after: bool,
+    /// Open the folder of the host platform's export in the file manager once packaging succeeded
+    #[arg(long)]
+    open_after: bool,
+    /// Push every export to itch.io with butler once packaging succeeded, to the `itch.project`
+    /// from xtask.toml and a channel per platform (the archives with --archive)
+    #[arg(long, conflicts_with = "pck_only")]
+    publish: bool,
+    /// Zip each export into builds/<platform>/<project>-<version>-<platform>.zip
+    #[arg(long, conflicts_with = "pck_only")]
+    archive: bool,
+    /// Version in the archive name (defaults to `config/version` in project.godot, then the Godot version)
+    #[arg(long, requires = "archive")]
+    archive_version: Option<String>,
+    /// Encrypt the exported pack with this 256-bit key (64 hex characters). Needs export
+    /// templates compiled with the same key
+    #[arg(long, env = "GODOT_SCRIPT_ENCRYPTION_KEY", hide_env_values = true, value_parser = parse_encryption_key)]
+    encryption_key: Option<String>,
+    /// Export with this template compiled from source instead of the downloaded one, e.g.
+    /// linux=../godot/bin/godot.linuxbsd.template_release.x86_64 (repeatable, the platform can be
+    /// left out when exporting one). Used as the debug template with --debug
+    #[arg(long = "custom-template", value_name = "[PLATFORM=]PATH", value_parser = parse_custom_template, conflicts_with_all = ["presets", "pck_only"])]
+    custom_templates: Vec<(Option<Platform>, PathBuf)>,
+    /// Export a dedicated server into builds/server/, with the crates' `server` cargo feature.
+    /// Their libraries are installed as <crate>_server, next to the regular ones
+    #[arg(long)]
+    server: bool,
+    /// Export the client into builds/<platform>-client/, with the crates' `client` cargo feature
+    /// and the `client` feature tag. Their libraries are installed as <crate>_client
+    #[arg(long, conflicts_with = "server")]
+    client: bool,
+    /// Base name of the exported executable, e.g. SuperGame for SuperGame.exe (defaults to
+    /// `export.binary_name`, then `config/name` of project.godot)
+    #[arg(long, value_parser = parse_binary_name)]
+    name: Option<String>,
+    /// Windows and Linux: embed the pack into the executable, for a single file to ship. The
+    /// game can't be patched with --pck-only then, every update replaces the whole executable
+    #[arg(long, overrides_with = "no_embed_pck", conflicts_with = "pck_only")]
+    embed_pck: bool,
+    /// Windows and Linux: keep the pack next to the executable (the default for generated
+    /// presets). Two files to ship, but a smaller executable and patches with --pck-only
+    #[arg(long, conflicts_with = "pck_only")]
+    no_embed_pck: bool,
+    /// Boot the export headless for a couple of frames and fail if it crashes, hangs or logs
+    /// an error (host platform only)
+    #[arg(long, conflicts_with = "pck_only")]
+    smoke_test: bool,
+    /// Scene the smoke test boots instead of the main scene, e.g. res://tests/boot_check.tscn
+    #[arg(long, requires = "smoke_test")]
+    smoke_test_scene: Option<String>,
+    /// Seconds the smoke test waits for the game to quit
+    #[arg(long, default_value_t = 30, requires = "smoke_test")]
+    smoke_test_timeout: u64,
+    /// Windows and Linux: make the export start in this locale, e.g. fr or pt_BR, through an
+    /// override.cfg next to the executable that sets `internationalization/locale/test`
+    #[arg(long, value_parser = parse_locale, conflicts_with = "pck_only")]
+    locale: Option<String>,
+    /// With --debug and --run-after: start the export connected to the debugger of an editor at
+    /// host:port, or a port on this machine
+    #[arg(long, value_parser = parse_remote_debug, requires_all = ["debug", "run_after"])]
+    remote_debug: Option<String>,
+    /// Delete each platform's folder, e.g. builds/linux/, before exporting into it, so nothing
+    /// of an earlier export is left over
+    #[arg(long)]
+    clean: bool,
+    /// Export this preset of a hand-written export_presets.cfg, to the preset's own export_path,
+    /// instead of generating presets (repeatable)
+    #[arg(long = "preset", conflicts_with_all = ["platforms", "server", "client"])]
+    presets: Vec<String>,
+    /// Print the presets in export_presets.cfg and exit
+    #[arg(long)]
+    list_presets: bool,
+    /// Try a failed export again up to this many times, removing its partial output in between,
+    /// e.g. when a virus scanner locks the new executable
+    #[arg(long, default_value_t = 0)]
+    export_retries: u32,
+    /// Don't import the assets before exporting, when the import cache in .godot/ is known to be current
+    #[arg(long)]
+    no_import: bool,
+    /// Export from a copy of the project under target/, so the import cache, generated presets
+    /// and anything else the export writes never touch the project folder
+    #[arg(long)]
+    isolated: bool,
+    /// Package even if the git checkout has uncommitted changes
+    #[arg(long)]
+    allow_dirty: bool,
+}
 
-    println!("Checking Export Templates...");
+impl PackageArgs {
+    /// Whether `--embed-pck` or `--no-embed-pck` was given, the last one winning.
+    fn embed_pck(&self) -> Option<bool> {
+        match (self.embed_pck, self.no_embed_pck) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
 
-    // Determine Godot's standard template path
-    let template_dir = get_godot_templates_dir()?;
-    let version_dir = template_dir.join(GODOT_VERSION_FULL);
+fn parse_binary_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        Err("expected a file name without a folder or extension".to_string())
+    } else {
+        Ok(name.to_string())
+    }
+}
 
-    if version_dir.exists() {
-        println!("Templates already installed at {:?}", version_dir);
-        return Ok(());
+fn parse_encryption_key(key: &str) -> Result<String, String> {
+    if key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(key.to_string())
+    } else {
+        Err("expected 64 hexadecimal characters (a 256-bit AES key)".to_string())
     }
+}
 
-    // Download the export templates
-    let version_tag = GODOT_VERSION;
-    let url = format!("{BASE_URL}/{version_tag}/Godot_v{version_tag}_export_templates.tpz");
+/// Options shared by every command that compiles the Rust crates.
+#[derive(Args, Clone, Default)]
+struct BuildArgs {
+    /// Rust target triple to build for, e.g. aarch64-unknown-linux-gnu (repeatable, defaults to the host)
+    #[arg(long = "target")]
+    targets: Vec<String>,
+    /// macOS only: build arm64 and x86_64 and merge them into a universal dylib with lipo
+    #[arg(long, conflicts_with = "targets")]
+    universal: bool,
+    /// Only build and install this extension crate (defaults to `build.crates` in xtask.toml, then every cdylib)
+    #[arg(long = "crate")]
+    crate_name: Option<String>,
+    /// Copy artifacts into game/bin even when they look unchanged
+    #[arg(long)]
+    force: bool,
+    /// Strip debug symbols from the installed library (default for release builds)
+    #[arg(long, conflicts_with = "no_strip")]
+    strip: bool,
+    /// Keep debug symbols in release libraries
+    #[arg(long)]
+    no_strip: bool,
+    /// macOS only: write a .dSYM bundle next to the built dylib before stripping it
+    #[arg(long)]
+    dsym: bool,
+    /// macOS only: keep the install name the linker gave the dylib instead of @rpath/<file>
+    #[arg(long)]
+    keep_install_name: bool,
+    /// Windows only: copy the .pdb next to the DLL for release builds too (always done for debug)
+    #[arg(long)]
+    with_symbols: bool,
+    /// entry_symbol of the generated .gdextension (defaults to gdext_rust_init)
+    #[arg(long)]
+    entry_symbol: Option<String>,
+    /// compatibility_minimum of the generated .gdextension (defaults to 4.1)
+    #[arg(long)]
+    compatibility_minimum: Option<String>,
+    /// Folder inside game/ the libraries are installed into, e.g. bin/dist (defaults to `extension.bin_dir`, i.e. bin)
+    #[arg(long)]
+    bin_dir: Option<String>,
+    /// Mark the extension reloadable so the editor picks up rebuilds (default for editor and watch)
+    #[arg(long, conflicts_with = "no_reloadable")]
+    reloadable: bool,
+    /// Don't mark the extension reloadable
+    #[arg(long)]
+    no_reloadable: bool,
+    /// Cargo features to enable, comma separated or repeated (passed to cargo build)
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+    /// Don't enable the crates' default features (passed to cargo build)
+    #[arg(long)]
+    no_default_features: bool,
+    /// Enable every feature of the built crates (passed to cargo build)
+    #[arg(long, conflicts_with = "features")]
+    all_features: bool,
+    /// Symlink the libraries into game/bin instead of copying them, so rebuilds show up without a copy (not for package)
+    #[arg(long)]
+    link: bool,
+    /// Compile through sccache (used automatically when it's on PATH and RUSTC_WRAPPER isn't set)
+    #[arg(long)]
+    sccache: bool,
+    /// Release builds only: fat LTO with a single codegen unit, for smaller and faster artifacts
+    #[arg(long)]
+    lto: bool,
+    /// Release builds only: opt-level of the release profile, overriding Cargo.toml
+    #[arg(long, value_parser = ["0", "1", "2", "3", "s", "z"])]
+    opt_level: Option<String>,
+    /// Cargo profile to build with, e.g. a `dist` profile from Cargo.toml. Anything but `dev` is
+    /// installed as the release library
+    #[arg(long)]
+    profile: Option<String>,
+    /// Number of parallel cargo jobs, e.g. 2 on a small CI runner (defaults to `build.jobs`, then one per CPU)
+    #[arg(short, long, env = "CARGO_BUILD_JOBS", value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+    /// Set an environment variable for cargo and Godot, e.g. API_URL=https://staging.example.com
+    /// (repeatable). Wins over the same variable from the shell
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env)]
+    env: Vec<(String, String)>,
+    /// Whether the command turns hot reload on when neither flag is given.
+    #[arg(skip)]
+    reloadable_by_default: bool,
+    /// Set by `package --server` and `--client`.
+    #[arg(skip)]
+    role: Role,
+}
 
-    println!("Downloading Export Templates from: {}", url);
-    let client = reqwest::blocking::Client::builder()
-        .timeout(None) // Disable timeout completely for large files
-        .build()?;
+impl BuildArgs {
+    fn should_strip(&self, release: bool) -> bool {
+        (release || self.strip) && !self.no_strip
+    }
 
-    let response = client.get(&url).send()?.bytes()?;
+    /// Name `extension`'s library is installed under. Only crates with the role's feature
+    /// build differently, the others keep their regular library.
+    fn library_name(&self, extension: &ExtensionCrate) -> String {
+        match self.role.name() {
+            Some(feature) if extension.features.iter().any(|declared| declared == feature) => self.role.library_name(&extension.name),
+            _ => extension.name.clone(),
+        }
+    }
 
-    println!("Extracting templates...");
-    let mut archive = zip::ZipArchive::new(Cursor::new(response))?;
+    /// Adds the feature selection flags to a cargo command.
+    fn apply_features(&self, cmd: &mut Command) {
+        if !self.features.is_empty() {
+            cmd.arg("--features").arg(self.features.join(","));
+        }
+        if self.no_default_features {
+            cmd.arg("--no-default-features");
+        }
+        if self.all_features {
+            cmd.arg("--all-features");
+        }
+    }
 
-    // Extract to a temporary folder first
-    let tmp_extract = root.join(".godot_bin/tmp_templates");
-    if tmp_extract.exists() { fs::remove_dir_all(&tmp_extract)?; }
-    archive.extract(&tmp_extract)?;
+    /// Limits how many jobs cargo runs at once.
+    fn apply_jobs(&self, cmd: &mut Command) {
+        if let Some(jobs) = self.jobs {
+            cmd.arg("--jobs").arg(jobs.to_string());
+        }
+    }
 
-    // Move to System Folder
-    fs::create_dir_all(&template_dir)?;
-    // The zip extracts a folder called "templates". We move/rename it.
-    let extracted_folder = tmp_extract.join("templates");
-    if !extracted_folder.exists() {
-        anyhow::bail!("Expected 'templates' folder in .tpz archive");
+    /// Makes cargo compile through sccache, returning whether it does. An existing RUSTC_WRAPPER
+    /// is left alone.
+    fn apply_sccache(&self, cmd: &mut Command) -> bool {
+        if std::env::var_os("RUSTC_WRAPPER").is_some() {
+            return false;
+        }
+        match find_sccache() {
+            Some(sccache) => {
+                debug!("Compiling through {:?}", sccache);
+                cmd.env("RUSTC_WRAPPER", sccache);
+                true
+            }
+            None => {
+                if self.sccache {
+                    warn!("--sccache: sccache is not installed (cargo install sccache), building without it");
+                }
+                false
+            }
+        }
     }
 
-    match fs::rename(&extracted_folder, &version_dir) {
-        Ok(_) => {},
-        Err(_) => {
-            // Fallback: Copy if rename fails (cross-device link)
-            let options = fs_extra::dir::CopyOptions::new().content_only(true);
-            fs::create_dir_all(&version_dir)?;
-            fs_extra::dir::copy(&extracted_folder, &version_dir, &options)?;
+    /// Whether the libraries are installed (and listed in the .gdextension) as the release
+    /// variant, which custom profiles are.
+    fn release_variant(&self, release: bool) -> bool {
+        match self.profile.as_deref() {
+            Some("dev") => false,
+            Some(_) => true,
+            None => release,
         }
     }
 
-    // Cleanup
-    fs::remove_dir_all(&tmp_extract)?;
+    /// The cargo profile a build uses.
+    fn cargo_profile(&self, release: bool) -> &str {
+        self.profile.as_deref().unwrap_or(if release { "release" } else { "dev" })
+    }
 
-    println!("Export Templates installed to {:?}", version_dir);
-    Ok(())
-}
+    /// Selects the cargo profile, with `--release` for the built-in one.
+    fn apply_cargo_profile(&self, cmd: &mut Command, release: bool) {
+        match self.cargo_profile(release) {
+            "dev" => {}
+            "release" => {
+                cmd.arg("--release");
+            }
+            profile => {
+                cmd.arg("--profile").arg(profile);
+            }
+        }
+    }
 
-fn generate_gdextension_file(game_dir: &Path, crate_name: &str) -> Result<()> {
-    let gdext_path = game_dir.join(format!("{}.gdextension", crate_name));
+    /// Overrides the profile through cargo's CARGO_PROFILE_<NAME>_* variables, so Cargo.toml
+    /// doesn't have to change. Debug builds are left alone.
+    fn apply_profile(&self, cmd: &mut Command, release: bool) {
+        if !self.lto && self.opt_level.is_none() {
+            return;
+        }
+        let profile = self.cargo_profile(release);
+        if profile == "dev" {
+            debug!("--lto and --opt-level only apply to release builds");
+            return;
+        }
 
-    let content = format!(r#"
-[configuration]
-entry_symbol = "gdext_rust_init"
-compatibility_minimum = "4.1"
+        let prefix = format!("CARGO_PROFILE_{}", profile.to_ascii_uppercase().replace('-', "_"));
+        let mut overrides = Vec::new();
+        if self.lto {
+            overrides.push((format!("{prefix}_LTO"), "fat"));
+            overrides.push((format!("{prefix}_CODEGEN_UNITS"), "1"));
+        }
+        if let Some(opt_level) = &self.opt_level {
+            overrides.push((format!("{prefix}_OPT_LEVEL"), opt_level.as_str()));
+        }
+        for (name, value) in overrides {
+            trace!("{}={}", name, value);
+            cmd.env(name, value);
+        }
+    }
 
-[libraries]
-linux.debug.x86_64 = "res://bin/{crate_name}/linux/lib{crate_name}.so"
-linux.release.x86_64 = "res://bin/{crate_name}/linux/lib{crate_name}.so"
-macos.debug.arm64 = "res://bin/{crate_name}/macos/arm64/lib{crate_name}.dylib"
-macos.release.arm64 = "res://bin/{crate_name}/macos/arm64/lib{crate_name}.dylib"
-windows.debug.x86_64 = "res://bin/{crate_name}/windows/{crate_name}.dll"
-windows.release.x86_64 = "res://bin/{crate_name}/windows/{crate_name}.dll"
-"#);
+    /// .gdextension settings from xtask.toml with the command line overrides applied.
+    fn extension_settings(&self, config: &Config) -> Result<ExtensionConfig> {
+        let mut settings = config.extension.clone();
+        if let Some(entry_symbol) = &self.entry_symbol {
+            settings.entry_symbol = entry_symbol.clone();
+        }
+        if let Some(compatibility_minimum) = &self.compatibility_minimum {
+            settings.compatibility_minimum = compatibility_minimum.clone();
+        }
+        if let Some(bin_dir) = &self.bin_dir {
+            settings.bin_dir = bin_dir.clone();
+        }
+        settings.reloadable = (self.reloadable || self.reloadable_by_default) && !self.no_reloadable;
+        settings.validate()?;
+        Ok(settings)
+    }
+}
 
-    fs::write(&gdext_path, content.trim())?;
-    println!("Generated .gdextension file at: {:?}", gdext_path);
+fn main() -> Result<()> {
+    // Parsed by hand to know which settings came from the environment, for `config`.
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    // Only the script may go to stdout, so this skips logging and the JSON events.
+    if let Commands::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "xtask", &mut std::io::stdout());
+        return Ok(());
+    }
+    init_logging(&cli);
+    report::set_format(cli.format);
+    interrupt::install();
+    let summarize = matches!(cli.command, Commands::Setup { .. } | Commands::Build { .. } | Commands::Package { .. });
 
-    Ok(())
+    let started = Instant::now();
+    let result = run(cli, &matches);
+    if summarize && result.is_ok() {
+        report::print_summary(started.elapsed());
+    }
+    report::finished(&result, started.elapsed());
+    result
 }
 
-fn build_and_install(root: &Path, release: bool) -> Result<()> {
-    println!("Building Rust crates...");
-    
-    let mut cmd = Command::new("cargo");
-    cmd.arg("build");
-    if release {
-        cmd.arg("--release");
+fn run(mut cli: Cli, matches: &ArgMatches) -> Result<()> {
+    let root = std::env::current_dir()?;
+    let mut config = Config::load(&root)?;
+    // Where the settings overridden below come from.
+    let mut origins = config_report::Origins::new();
+    // First, everything else reads from the project.
+    if let Some(project_dir) = cli.project_dir.take() {
+        config.project.dir = project_dir;
+        let origin = match matches.value_source("project_dir") {
+            Some(ValueSource::EnvVariable) => Origin::Env("RUSTYGODOT_PROJECT_DIR"),
+            _ => Origin::Flag("--project-dir"),
+        };
+        origins.insert("project.dir", origin);
+    }
+    for (set, key, flag) in [
+        (cli.use_system, "godot.use_system", "--use-system"),
+        (cli.force_system, "godot.force_system", "--force-system"),
+        (cli.mono, "godot.mono", "--mono"),
+    ] {
+        if set {
+            origins.insert(key, Origin::Flag(flag));
+        }
+    }
+    config.godot.use_system |= cli.use_system;
+    config.godot.force_system |= cli.force_system;
+    config.godot.mono |= cli.mono;
+    config.godot.verbose = cli.godot_verbose;
+    if let Some(version) = cli.godot_version {
+        config.godot.version = version;
+        config.godot.version_pinned = true;
+        origins.insert("godot.version", Origin::Flag("--godot-version"));
     }
-    
-    let status = cmd.status()?;
-    if !status.success() {
-        anyhow::bail!("Cargo build failed");
+    // The project itself knows which engine it was made for. A version that differs is
+    // reported by the commands that run it, see `check_project_features`.
+    if let Some(project_version) = godot::project_engine_version(&config.game_dir(&root)) {
+        if !config.godot.version_pinned {
+            config.godot.version = format!("{project_version}-stable");
+            origins.insert("godot.version", Origin::Project);
+        }
+    }
+    if let Some(godot_dir) = cli.godot_dir {
+        config.godot.install_dir = godot_dir;
+        let origin = match matches.value_source("godot_dir") {
+            Some(ValueSource::EnvVariable) => Origin::Env("RUSTYGODOT_GODOT_DIR"),
+            _ => Origin::Flag("--godot-dir"),
+        };
+        origins.insert("godot.install_dir", origin);
+    }
+    if let Some(name) = cli.project_name {
+        config.project.name = name;
+        origins.insert("project.name", Origin::Flag("--project-name"));
+    }
+    if cli.no_hooks {
+        config.hooks = HooksConfig::default();
+        for key in ["hooks.pre_build", "hooks.post_build", "hooks.pre_package", "hooks.post_package"] {
+            origins.insert(key, Origin::Flag("--no-hooks"));
+        }
+    }
+    if let Some(build) = cli.command.build_args_mut() {
+        build.jobs = build.jobs.or(config.build.jobs);
+        // Set before any process or thread starts, so cargo, Godot and the hooks all inherit
+        // them, like the NDK linker variables.
+        for (key, value) in &build.env {
+            debug!("--env {}", key);
+            std::env::set_var(key, value);
+        }
     }
+    DRY_RUN.store(cli.dry_run, Ordering::Relaxed);
+    TIMEOUT.store(cli.timeout.unwrap_or(0), Ordering::Relaxed);
+    OFFLINE.store(cli.offline, Ordering::Relaxed);
 
-    // Move Artifacts
-    let target_dir = root.join("target").join(if release { "release" } else { "debug" });
-    let output_dir = root.join("game/bin/game");
-    
-    if !output_dir.exists() {
-        fs::create_dir_all(&output_dir)?;
+    match cli.command {
+        Commands::New { name, force } => {
+            if !dry_run(format_args!("scaffold a new project in {:?}", root.join(&name))) {
+                scaffold::new_project(&root.join(&name), &name, force)?;
+            }
+        }
+        Commands::Init { force } => {
+            let name = root
+                .file_name()
+                .and_then(|name| name.to_str())
+                .context("Can't derive a project name from the current directory")?
+                .to_string();
+            if !dry_run(format_args!("scaffold a new project in {:?}", root)) {
+                scaffold::new_project(&root, &name, force)?;
+            }
+        }
+        Commands::Setup { mut setup } => {
+            if setup.interactive {
+                interactive_setup(&mut config, &mut setup)?;
+            }
+            if setup.clear_cache {
+                download::clear_cache()?;
+            } else {
+                setup_godot(&root, &config, &setup, cli.frozen)?;
+            }
+        }
+        Commands::InitGitignore => scaffold::update_gitignore(&root, &config)?,
+        Commands::Config => config_report::show_config(&root, &config, &origins)?,
+        Commands::List => list_installed(&root, &config)?,
+        Commands::Templates => list_templates(&config)?,
+        Commands::ListTargets => targets::list_targets(&config)?,
+        Commands::Version => print_versions(&root, &config),
+        Commands::Examples => examples::list_examples(&config.game_dir(&root), &config.examples)?,
+        Commands::Uninstall { version, templates, editor, yes } => {
+            if let Some(version) = version {
+                config.godot.version = version;
+                config.godot.version_pinned = true;
+            }
+            // Neither flag means both.
+            let (remove_editor, remove_templates) = (editor || !templates, templates || !editor);
+            uninstall(&root, &config, remove_editor, remove_templates, yes)?;
+        }
+        Commands::Build { release, build } => {
+            let metadata = Metadata::load(&root)?;
+            report::timed("build", || build_and_install(&root, &config, &metadata, release, &build))?;
+        }
+        Commands::Editor { mut build, scene, no_build, detach, game_args } => {
+            build.reloadable_by_default = true;
+            let metadata = Metadata::load(&root)?;
+            if no_build {
+                check_installed(&root, &config, &metadata, false, &build)?;
+            } else {
+                build_and_install(&root, &config, &metadata, false, &build)?;
+            }
+            check_editor_arch(&root, &config, &metadata, &build)?;
+            check_project_features(&root, &config, cli.update_project_features)?;
+            run_godot(&root, &config, true, scene.as_deref(), &LaunchArgs::default(), &game_args, detach)?;
+        }
+        Commands::Watch { mut build, assets } => {
+            build.reloadable_by_default = true;
+            watch::watch(&root, &config, &build, assets)?;
+        }
+        Commands::Run { build, scene, example, no_build, detach, profile_startup, launch, game_args } => {
+            // Resolved first, a typo shouldn't wait for the build.
+            let scene = match example {
+                Some(name) => Some(examples::scene(&config.game_dir(&root), &config.examples, &name)?),
+                None => scene,
+            };
+            let metadata = Metadata::load(&root)?;
+            if no_build {
+                check_installed(&root, &config, &metadata, false, &build)?;
+            } else {
+                build_and_install(&root, &config, &metadata, false, &build)?;
+            }
+            check_editor_arch(&root, &config, &metadata, &build)?;
+            check_project_features(&root, &config, cli.update_project_features)?;
+            if profile_startup {
+                let log_path = metadata.target_directory.join(startup_profile::LOG_FILE);
+                startup_profile::profile_startup(&root, &config, scene.as_deref(), &launch, &game_args, &log_path)?;
+            } else {
+                run_godot(&root, &config, false, scene.as_deref(), &launch, &game_args, detach)?;
+            }
+        },
+        Commands::Test { script, scene, no_import, build } => {
+            let metadata = Metadata::load(&root)?;
+            build_and_install(&root, &config, &metadata, false, &build)?;
+            if !no_import {
+                import_assets(&root, &config)?;
+            }
+            let code = run_tests(&root, &config, script, scene)?;
+            report::event("test", json!({ "exit_code": code }));
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Package { package, mut build } => {
+            if package.list_presets {
+                return list_presets(&config.game_dir(&root));
+            }
+            lock::check(&root, &config.godot, cli.frozen)?;
+            build_info::check_clean(&root, &config.game_dir(&root), package.allow_dirty)?;
+            let metadata = Metadata::load(&root)?;
+            // Exports must contain the real file, not a link into target/.
+            if build.link {
+                warn!("package always copies the libraries, ignoring --link");
+                build.link = false;
+            }
+            // Applied to the config so the generated presets point at the same place.
+            if let Some(out) = &package.out {
+                config.export.builds_dir = out.clone();
+            }
+            if !package.feature_tags.is_empty() {
+                config.export.custom_features = package.feature_tags.clone();
+            }
+            if !package.include.is_empty() {
+                config.export.include_filter = package.include.clone();
+            }
+            if !package.exclude.is_empty() {
+                config.export.exclude_filter = package.exclude.clone();
+            }
+            config.export.encrypt = package.encryption_key.is_some();
+            config.export.embed_pck = package.embed_pck();
+            // Hand-written presets are checked before anything is built.
+            let user_targets = if package.presets.is_empty() {
+                None
+            } else {
+                Some(user_preset_targets(&config.game_dir(&root), &package)?)
+            };
+            let platforms = if let Some(targets) = &user_targets {
+                let mut platforms: Vec<Platform> = Vec::new();
+                for target in targets {
+                    if !platforms.contains(&target.platform) {
+                        platforms.push(target.platform);
+                    }
+                }
+                platforms
+            } else if !package.platforms.is_empty() {
+                package.platforms.clone()
+            } else if !config.export.platforms.is_empty() {
+                config.export.platforms.clone()
+            } else {
+                vec![Platform::host()]
+            };
+            if package.server {
+                enable_role_build(&mut config, &metadata, &platforms, &mut build, Role::Server)?;
+            } else if package.client {
+                enable_role_build(&mut config, &metadata, &platforms, &mut build, Role::Client)?;
+            }
+            config.export.custom_templates = custom_templates(&root, &platforms, &package.custom_templates)?;
+            config.export.archs = export_archs(&platforms, &build)?;
+            // Checked before the build, the project is only needed at the very end.
+            if package.publish {
+                itch::project(&config)?;
+            }
+            // A pack export doesn't go through the export templates.
+            if !package.pck_only {
+                ensure_export_templates(&root, &config, &platforms, package.debug)?;
+            }
+            if package.no_build {
+                check_installed(&root, &config, &metadata, !package.debug, &build)?;
+            } else {
+                report::timed("build", || build_and_install(&root, &config, &metadata, !package.debug, &build))?;
+            }
+            if platforms.contains(&Platform::Android) && !package.no_build {
+                report::timed("android build", || build_android_and_install(&root, &config, &metadata, !package.debug, &build))?;
+            }
+            if platforms.contains(&Platform::Web) && !package.no_build {
+                report::timed("web build", || build_web_and_install(&root, &config, &metadata, !package.debug, &build))?;
+            }
+            bootstrap_project(&config.game_dir(&root), &config)?;
+            check_project_features(&root, &config, cli.update_project_features)?;
+            if let Some(name) = &package.name {
+                config.export.binary_name = Some(name.clone());
+            } else if let Some(name) = &config.export.binary_name {
+                parse_binary_name(name).map_err(|err| anyhow::anyhow!("export.binary_name '{}': {}", name, err))?;
+            } else {
+                // Read after the bootstrap, which writes config/name to a new project.
+                let project_name = godot::project_setting(&config.game_dir(&root), "config/name").unwrap_or(config.project.name.clone());
+                config.export.binary_name = Some(archive::slug(&project_name)).filter(|slug| !slug.is_empty());
+            }
+            if package.isolated {
+                // Everything below, the presets and the import cache included, goes to the copy.
+                let staged = metadata.target_directory.join("xtask-package");
+                assets::mirror_project(&config.game_dir(&root), &staged, &[".godot"])?;
+                info!("Exporting from a copy of the project in {:?}", staged);
+                config.project.dir = staged;
+            }
+            if platforms.contains(&Platform::Macos) {
+                macos::prepare_bundle(&config.game_dir(&root), &mut config.macos)?;
+            }
+            let targets = match user_targets {
+                Some(targets) => targets,
+                None => {
+                    ensure_export_presets(&root, &config, &platforms, package.debug)?;
+                    generated_preset_targets(&root, &config, &platforms, &package)
+                }
+            };
+            package_game(&root, &config, &targets, &package)?;
+        }
+        Commands::Godot { args } => {
+            let code = run_godot_passthrough(&root, &config, &args)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::SelfUpdate { feed, check } => self_update::self_update(&feed, check)?,
+        Commands::Completions { .. } => unreachable!("completions are printed before the config is loaded"),
     }
 
-    let (ext, crate_name, output_dir ) = if cfg!(target_os = "windows") {
-        ("dll", "game", output_dir.join("windows"))
-    } else if cfg!(target_os = "linux") {
-        ("so", "libgame", output_dir.join("linux"))
-    } else if cfg!(target_os = "macos") {
-        ("dylib", "libgame", output_dir.join("macos").join("arm64"))
-    } else {
-        anyhow::bail!("Unsupported OS");
+    Ok(())
+}
+
+fn init_logging(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
     };
 
-    if !output_dir.exists(){
-        fs::create_dir_all(&output_dir)?;
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level);
+    if cli.format == OutputFormat::Json {
+        // Messages become events too, so every line on stdout is JSON.
+        builder.target(env_logger::Target::Stdout).format(|buf, record| {
+            let level = record.level().as_str().to_ascii_lowercase();
+            writeln!(buf, "{}", json!({ "event": "log", "level": level, "message": record.args().to_string() }))
+        });
+    } else {
+        // Regular progress messages read like plain output, everything else is tagged with its level.
+        builder.format(|buf, record| match record.level() {
+            log::Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "[{}] {}", level, record.args()),
+        });
     }
+    builder.init();
+}
 
-    // Copy specific crate output
-    let src = target_dir.join(format!("{}.{}", crate_name, ext));
-    let dst = output_dir.join(format!("{}.{}", crate_name, ext));
+/// Set once from `--dry-run`, like the log level, so it doesn't have to be passed everywhere.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
 
-    if src.exists() {
-        fs::copy(&src, &dst)?;
-        println!("Copied artifact to {:?}", dst);
-        // Generate the configuration
-        let game_dir = root.join("game");
-        generate_gdextension_file(&game_dir, "game")?;
-    } else {
-        anyhow::bail!("Failed to find artifact: {:?}", src);
+/// With `--dry-run`, reports the side effect instead and returns true so the caller skips it.
+fn dry_run(action: std::fmt::Arguments) -> bool {
+    let dry_run = DRY_RUN.load(Ordering::Relaxed);
+    if dry_run {
+        info!("[dry-run] Would {}", action);
     }
-
-    Ok(())
+    dry_run
 }
 
-fn run_godot(root: &Path, editor: bool) -> Result<()> {
-    let (_, bin_relative_path) = get_os_info();
-    let bin_dir = root.join(".godot_bin");
-    let godot_exe = bin_dir.join(bin_relative_path);
+/// Set once from `--timeout`, in seconds. 0 is no limit.
+static TIMEOUT: AtomicU64 = AtomicU64::new(0);
 
-    if !godot_exe.exists() {
-        anyhow::bail!("Godot executable not found. Run 'cargo xtask setup' first.");
-    }
+fn godot_timeout() -> Option<Duration> {
+    Some(TIMEOUT.load(Ordering::Relaxed)).filter(|secs| *secs > 0).map(Duration::from_secs)
+}
 
-    let game_dir = root.join("game");
-    if !game_dir.exists() {
-        fs::create_dir_all(&game_dir)?;
-    }
+/// Runs a Godot process to the end, or for `--timeout` at most. `what` names it in the error.
+fn wait_for_godot(cmd: &mut Command, what: &str) -> Result<std::process::ExitStatus> {
+    let Some(timeout) = godot_timeout() else {
+        return interrupt::status(cmd).with_context(|| format!("Failed to start {}", what));
+    };
+    interrupt::status_with_timeout(cmd, timeout)
+        .with_context(|| format!("Failed to start {}", what))?
+        .with_context(|| format!("{} was still running after {}s and was stopped (--timeout)", what, timeout.as_secs()))
+}
 
-    // Auto-Create 'project.godot' if missing
-    // This prevents the "Project Manager" wizard from appearing and complaining.
-    let project_file = game_dir.join("project.godot");
-    if !project_file.exists() {
-        println!("project.godot missing. Creating minimal project...");
+/// Set once from `--offline` or RUSTYGODOT_OFFLINE.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
 
-        // Minimal Godot 4.6 config
-        let content = r#"; Engine configuration file.
-config_version=5
+/// Whether anything needing the network should fail right away.
+fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
 
-[application]
-config/name="My Rust Game"
-config/features=PackedStringArray("4.6", "Forward Plus")
-config/icon="res://icon.svg"
+/// Logs the full command line of an external program about to be run (shown with -vv).
+fn trace_command(cmd: &Command) {
+    trace!("Running {:?}", cmd);
+}
 
-[dotnet]
-project/assembly_name="My Rust Game"
-"#;
-        let mut file = fs::File::create(&project_file)?;
-        file.write_all(content.as_bytes())?;
-    }
+/// Editor archive suffix and the path of the binary inside the extracted archive.
+fn get_os_info(godot: &GodotConfig) -> (&'static str, String) {
+    let version = &godot.version;
 
-    // Get Absolute Paths
-    let godot_exe_abs = godot_exe.canonicalize()
-        .context("Failed to canonicalize Godot executable path")?;
+    // The .NET builds extract into a folder (next to the GodotSharp assemblies) instead of
+    // putting the binary at the archive root.
+    if godot.mono {
+        return if cfg!(target_os = "windows") {
+            ("mono_win64.zip", format!("Godot_v{version}_mono_win64/Godot_v{version}_mono_win64.exe"))
+        } else if cfg!(target_os = "macos") {
+            ("mono_macos.universal.zip", "Godot_mono.app/Contents/MacOS/Godot".to_string())
+        } else {
+            ("mono_linux_x86_64.zip", format!("Godot_v{version}_mono_linux_x86_64/Godot_v{version}_mono_linux.x86_64"))
+        };
+    }
 
-    let game_dir_abs = game_dir.canonicalize()?;
+    if cfg!(target_os = "windows") {
+        ("win64.exe.zip", format!("Godot_v{version}_win64.exe"))
+    } else if cfg!(target_os = "macos") {
+        ("macos.universal.zip", "Godot.app/Contents/MacOS/Godot".to_string())
+    } else {
+        ("linux.x86_64.zip", format!("Godot_v{version}_linux.x86_64"))
+    }
+}
 
-    let mut cmd = Command::new(&godot_exe_abs);
+fn setup_godot(root: &Path, config: &Config, args: &SetupArgs, frozen: bool) -> Result<()> {
+    let bin_dir = config.godot.bin_dir(root);
+    let previous = lock::check(root, &config.godot, frozen)?;
 
-    // This makes Godot treat 'game/' as the root, avoiding the "working directory" error.
-    cmd.current_dir(&game_dir_abs);
+    // Download Editor
+    let url = editor_url(&config.godot);
 
-    if editor {
-        cmd.arg("-e");
+    if dry_run(format_args!("download {} and extract it into {:?}", url, bin_dir)) {
+        if args.no_templates {
+            return Ok(());
+        }
+        return install_export_templates(root, config, args.force, args.update_templates, !args.no_cache);
     }
 
-    cmd.arg("--path").arg(&game_dir_abs);
+    check_setup_space(root, config, args, &url)?;
+    if args.force {
+        remove_editor_install(config, &bin_dir, &url)?;
+    }
 
-    println!("Launching Godot...");
-    cmd.status().context("Failed to launch Godot process")?;
+    // The editor and the much bigger templates download at the same time, and each is extracted
+    // as soon as it's there. The templates are still only fetched if they aren't installed.
+    std::thread::scope(|scope| {
+        let templates = scope.spawn(|| {
+            if args.no_templates {
+                return Ok(());
+            }
+            install_export_templates(root, config, args.force, args.update_templates, !args.no_cache)
+        });
+        let editor = install_editor(root, config, args, &url);
+        let templates = templates
+            .join()
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("Installing the export templates panicked")));
+        editor.and(templates)
+    })?;
 
-    Ok(())
+    // The checksums come from the download cache, which has every archive setup verified.
+    let version = &config.godot.version;
+    let checksums = download::cached_checksum(&url, version).zip(download::cached_checksum(&templates_url(&config.godot), version));
+    let Some((editor_sha512, templates_sha512)) = checksums else {
+        if frozen {
+            anyhow::bail!("--frozen: the installed archives aren't in the download cache, so they can't be checked against {}", lock::LOCK_FILE);
+        }
+        warn!("The installed archives aren't in the download cache, not updating {}. Run setup with --no-cache to lock them.", lock::LOCK_FILE);
+        return Ok(());
+    };
+    let installed = lock::Lock {
+        godot: lock::LockedGodot {
+            version: version.clone(),
+            mono: config.godot.mono,
+            editor_sha512,
+            templates_version: config.godot.templates_version(),
+            templates_sha512,
+        },
+    };
+    lock::update(root, previous.as_ref(), installed, frozen)
 }
 
-fn ensure_export_presets(game_dir: &Path) -> Result<()> {
-    let presets_path = game_dir.join("export_presets.cfg");
-    if presets_path.exists() {
+/// For `setup --force`: deletes the installed editor, so nothing of a broken install is left
+/// once it's extracted again. Offline, that's only done if the cache can replace it.
+fn remove_editor_install(config: &Config, bin_dir: &Path, editor_url: &str) -> Result<()> {
+    if !bin_dir.exists() {
         return Ok(());
     }
+    if offline() && download::cached_size(editor_url, &config.godot.version).is_none() {
+        anyhow::bail!(
+            "--force: the editor isn't in the download cache, so it can't be reinstalled offline. Run setup --force without --offline."
+        );
+    }
+    info!("--force: removing {:?}", bin_dir);
+    fs::remove_dir_all(bin_dir).with_context(|| format!("Failed to remove {:?}", bin_dir))
+}
 
-    println!("Generating export_presets.cfg...");
-
-    // Generate a preset for the current OS so 'package' works out of the box.
-    let (platform_name, _) = get_platform_export_name();
+/// How much bigger the editor and templates get once extracted, roughly and on the safe side.
+const EXTRACTED_RATIO: u64 = 2;
 
-    let content = format!(r#"
-[preset.0]
+/// Checks that the downloads and their extracted files fit on disk before anything is
+/// downloaded, instead of running out of space halfway through the extraction.
+fn check_setup_space(root: &Path, config: &Config, args: &SetupArgs, editor_url: &str) -> Result<()> {
+    let version = &config.godot.version;
+    let bin_dir = config.godot.bin_dir(root);
+    let cache = download::cache_dir()?.join(version);
+    let template_dir = get_godot_templates_dir()?;
+    let version_dir = template_dir.join(config.godot.templates_version());
+    let templates_needed =
+        !args.no_templates && (args.force || !(version_dir.exists() && missing_template_files(&version_dir).is_empty()));
 
-name="{platform_name}"
-platform="{platform_name}"
-runnable=true
-custom_features=""
-export_filter="all_resources"
-include_filter=""
-exclude_filter=""
-export_path="../builds/{platform_name}/game"
-patch_list=PackedStringArray()
-"#);
+    let mut downloads = vec![(editor_url.to_string(), false)];
+    if templates_needed {
+        downloads.push((templates_url(&config.godot), true));
+    }
 
-    fs::write(&presets_path, content.trim())?;
-    Ok(())
+    let mut needs = Vec::new();
+    for (url, templates) in &downloads {
+        let cached = if args.no_cache { None } else { download::cached_size(url, version) };
+        let size = match cached {
+            Some(size) => size,
+            None => {
+                // Without a size there's nothing to estimate; the server may not send one.
+                let Some(size) = download::remote_size(url) else { continue };
+                needs.push((cache.clone(), size));
+                size
+            }
+        };
+        // Both are extracted into the install folder, the templates to a temporary folder first.
+        needs.push((bin_dir.clone(), size * EXTRACTED_RATIO));
+        if *templates && !disk::same_file_system(&bin_dir, &template_dir) {
+            // Moving them across file systems copies them.
+            needs.push((template_dir.clone(), size * EXTRACTED_RATIO));
+        }
+    }
+    disk::check_space(&needs)
 }
 
-fn package_game(root: &Path) -> Result<()> {
-    let (_, bin_relative_path) = get_os_info();
-    let godot_exe = root.join(".godot_bin").join(bin_relative_path);
-    let game_dir = root.join("game");
+/// Asks for what `setup` would otherwise take from the flags and xtask.toml, prefilled with
+/// those. Without a terminal nothing is asked, so scripts and CI are never stuck on a prompt.
+fn interactive_setup(config: &mut Config, args: &mut SetupArgs) -> Result<()> {
+    use std::io::IsTerminal;
 
-    // Ensure build output directory exists
-    let builds_dir = root.join("builds");
-    if !builds_dir.exists() {
-        fs::create_dir(&builds_dir)?;
+    if !std::io::stdin().is_terminal() {
+        info!("--interactive: not running in a terminal, using the flags and xtask.toml");
+        return Ok(());
     }
 
-    let (platform_name, output_ext) = get_platform_export_name();
-    let output_path = builds_dir.join(platform_name).join(format!("game{}", output_ext));
-
-    // Create the specific platform folder (e.g., builds/Linux)
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent)?;
+    let version = prompt("Godot version", &config.godot.version)?;
+    if version != config.godot.version {
+        config.godot.version = version;
+        config.godot.version_pinned = true;
     }
+    let templates = prompt("Install the export templates, needed by 'package' (y/n)", if args.no_templates { "n" } else { "y" })?;
+    args.no_templates = !matches!(templates.as_str(), "y" | "Y" | "yes");
+    config.godot.install_dir = PathBuf::from(prompt("Install folder", &config.godot.install_dir.display().to_string())?);
+    Ok(())
+}
 
-    println!("Exporting project for {}...", platform_name);
+/// Asks for a value on the terminal, returning `default` when the answer is empty.
+fn prompt(question: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", question, default);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
 
-    let godot_abs = godot_exe.canonicalize()?;
-    let game_abs = game_dir.canonicalize()?;
-    let output_abs = output_path; // Don't canonicalize yet, might not exist
+fn editor_url(godot: &GodotConfig) -> String {
+    let (zip_suffix, _) = get_os_info(godot);
+    let base_url = &godot.mirror;
+    let version_tag = &godot.version;
+    format!("{base_url}/{version_tag}/Godot_v{version_tag}_{zip_suffix}")
+}
 
-    println!("Step 1/2: Importing assets for {}...", platform_name);
-    let status_import = Command::new(&godot_abs)
-        .arg("--headless")
-        .arg("--editor")
-        .arg("--quit") // Quit immediately after import
-        .arg("--audio-driver").arg("Dummy")
-        .arg("--display-driver").arg("headless")
-        .current_dir(&game_abs)
-        .status()?;
+fn templates_url(godot: &GodotConfig) -> String {
+    let base_url = &godot.mirror;
+    let version_tag = &godot.version;
+    let mono = if godot.mono { "_mono" } else { "" };
+    format!("{base_url}/{version_tag}/Godot_v{version_tag}{mono}_export_templates.tpz")
+}
 
-    if !status_import.success() {
-        anyhow::bail!("Godot Import step failed.");
-    }
+/// Downloads and extracts the editor into `bin_dir`, and checks that it runs.
+fn install_editor(root: &Path, config: &Config, args: &SetupArgs, url: &str) -> Result<()> {
+    let (_, bin_relative_path) = get_os_info(&config.godot);
+    let bin_dir = config.godot.bin_dir(root);
+    let version_tag = &config.godot.version;
 
-    println!("Step 2/2: Exporting project for {}...", platform_name);
-    let status_export = Command::new(&godot_abs)
-        .arg("--headless")
-        .arg("--audio-driver").arg("Dummy")
-        .arg("--display-driver").arg("headless")
-        .arg("--export-release")
-        .arg(platform_name)
-        .arg(output_abs)
-        .current_dir(&game_abs)
-        .status()?;
+    // The templates may have created it already.
+    fs::create_dir_all(&bin_dir)?;
 
-    if status_export.success() {
-        println!("Export complete! Find it at: builds/{}/", platform_name);
+    let binary_path = bin_dir.join(&bin_relative_path);
+    if offline() && binary_path.exists() {
+        info!("Offline: keeping the editor already installed at {:?}", binary_path);
     } else {
-        anyhow::bail!("Godot Export step failed.");
+        let response = report::timed("download editor", || download::fetch_archive(url, version_tag, !args.no_cache))?;
+
+        info!("Extracting...");
+        report::timed("extract editor", || extract::extract(&response, &bin_dir))?;
+    }
+
+    if !binary_path.exists() {
+        anyhow::bail!("Extracted binary not found at {:?}", binary_path);
+    }
+
+    // Fix Permissions (Linux & Mac)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut perms = fs::metadata(&binary_path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&binary_path, perms)?;
+        info!("Fixed permissions for: {:?}", binary_path);
+
+        // MAC SPECIFIC: Remove the "Quarantine" attribute
+        // macOS blocks downloaded binaries by default (Gatekeeper).
+        #[cfg(target_os = "macos")]
+        {
+            let mut cmd = Command::new("xattr");
+            cmd.arg("-d")
+                .arg("com.apple.quarantine")
+                .arg(&bin_dir.join(bin_relative_path.split('/').next().unwrap_or("Godot.app")))
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null());
+            trace_command(&cmd);
+            let _ = cmd.status();
+        }
+    }
+
+    // A truncated download, a binary for another CPU or a leftover quarantine flag only shows up
+    // once the binary actually runs.
+    let reported = godot::reported_version(&binary_path)
+        .with_context(|| format!("The downloaded Godot binary doesn't run. Delete {:?} and run 'cargo xtask setup' again.", bin_dir))?;
+    if !godot::is_configured_version(&reported, config) {
+        anyhow::bail!(
+            "The downloaded Godot binary reports version {}, but {} is configured",
+            reported,
+            config.godot.version
+        );
+    }
+    info!("Godot {} Setup Complete at {:?}", reported, bin_dir);
+    report::event("setup", json!({ "version": reported, "path": bin_dir }));
+    Ok(())
+}
+
+/// Downloads the export templates into Godot's templates folder. An existing install
+/// of the same version is kept unless `replace` is set.
+///
+/// With `update`, the templates of the same or a close version already installed are updated
+/// in place, the ones of the close version moving to the new version's folder.
+fn install_export_templates(root: &Path, config: &Config, replace: bool, update: bool, use_cache: bool) -> Result<()> {
+    info!("Checking Export Templates...");
+
+    // Determine Godot's standard template path
+    let template_dir = get_godot_templates_dir()?;
+    let version_dir = template_dir.join(config.godot.templates_version());
+
+    let mut replace = replace;
+    if version_dir.exists() && !replace {
+        let missing = missing_template_files(&version_dir);
+        if missing.is_empty() {
+            info!("Templates already installed at {:?}", version_dir);
+            return Ok(());
+        }
+
+        warn!("Templates at {:?} are incomplete, missing {}", version_dir, missing.join(", "));
+        if !confirm("Reinstall the export templates?")? {
+            return Ok(());
+        }
+        replace = true;
+    }
+
+    // Download the export templates
+    let version_tag = &config.godot.version;
+    let url = templates_url(&config.godot);
+
+    let base_dir = if !update {
+        None
+    } else if version_dir.exists() {
+        Some(version_dir.clone())
+    } else {
+        close_templates_dir(&template_dir, &config.godot.templates_version())
+    };
+    if let Some(base_dir) = &base_dir {
+        info!("Updating the export templates at {:?} to {}", base_dir, version_tag);
+    } else if update {
+        info!("No export templates of a close version to update, installing them in full");
+    }
+
+    if dry_run(format_args!("download {} and install it into {:?}", url, version_dir)) {
+        return Ok(());
+    }
+
+    let response = report::timed("download export templates", || download::fetch_archive(&url, version_tag, use_cache))?;
+
+    info!("Extracting templates...");
+
+    // Extract to a temporary folder first
+    let tmp_extract = config.godot.bin_dir(root).join("tmp_templates");
+    fs::create_dir_all(config.godot.bin_dir(root))?;
+    if tmp_extract.exists() { fs::remove_dir_all(&tmp_extract)?; }
+    let _tmp_guard = interrupt::temp_path(&tmp_extract);
+    report::timed("extract export templates", || extract::extract(&response, &tmp_extract))?;
+
+    // Move to System Folder
+    fs::create_dir_all(&template_dir)?;
+    // The zip extracts a folder called "templates". We move/rename it.
+    let extracted_folder = tmp_extract.join("templates");
+    if !extracted_folder.exists() {
+        anyhow::bail!("Expected 'templates' folder in .tpz archive");
+    }
+    let missing = missing_template_files(&extracted_folder);
+    if !missing.is_empty() {
+        anyhow::bail!("The .tpz archive is incomplete, it has no {}", missing.join(", "));
+    }
+
+    if let Some(base_dir) = base_dir {
+        if base_dir != version_dir {
+            fs::rename(&base_dir, &version_dir)
+                .with_context(|| format!("Failed to move {:?} to {:?}", base_dir, version_dir))?;
+        }
+        let (replaced, removed) = report::timed("update export templates", || update_templates(&extracted_folder, &version_dir))?;
+        fs::remove_dir_all(&tmp_extract)?;
+        info!("Export Templates updated at {:?}: {} file(s) replaced, {} removed", version_dir, replaced, removed);
+        return Ok(());
+    }
+
+    // Only drop the old install once the new one is known to be good.
+    if replace && version_dir.exists() {
+        fs::remove_dir_all(&version_dir)?;
+    }
+
+    match fs::rename(&extracted_folder, &version_dir) {
+        Ok(_) => {},
+        Err(_) => {
+            // Fallback: Copy if rename fails (cross-device link)
+            let options = fs_extra::dir::CopyOptions::new().content_only(true);
+            fs::create_dir_all(&version_dir)?;
+            fs_extra::dir::copy(&extracted_folder, &version_dir, &options)?;
+        }
+    }
+
+    // Cleanup
+    fs::remove_dir_all(&tmp_extract)?;
+
+    info!("Export Templates installed to {:?}", version_dir);
+    Ok(())
+}
+
+/// A complete install of another version with the same major and minor number, e.g.
+/// "4.6.0-stable" for "4.6.1-stable", the newest one if there are several.
+fn close_templates_dir(template_dir: &Path, folder: &str) -> Option<PathBuf> {
+    // "4.6.1-stable" -> "4.6", and .NET templates only count for .NET templates.
+    let series = |name: &str| {
+        let mut parts = name.split(['.', '-']);
+        let major = parts.next().filter(|part| part.parse::<u32>().is_ok())?;
+        let minor = parts.next().filter(|part| part.parse::<u32>().is_ok())?;
+        Some((format!("{major}.{minor}"), name.ends_with(".mono")))
+    };
+    let wanted = series(folder)?;
+    fs::read_dir(template_dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name != folder && series(name).as_ref() == Some(&wanted))
+        .filter(|name| missing_template_files(&template_dir.join(name)).is_empty())
+        .max()
+        .map(|name| template_dir.join(name))
+}
+
+/// Makes `installed` match `extracted`: files whose checksum differs are replaced, files the
+/// new templates don't have are removed, and the rest isn't written at all. Returns how many
+/// files were replaced and removed.
+fn update_templates(extracted: &Path, installed: &Path) -> Result<(usize, usize)> {
+    let (mut replaced, mut removed) = (0, 0);
+    fs::create_dir_all(installed)?;
+    for entry in fs::read_dir(extracted)? {
+        let entry = entry?;
+        let source = entry.path();
+        let target = installed.join(entry.file_name());
+        if source.is_dir() {
+            let (dir_replaced, dir_removed) = update_templates(&source, &target)?;
+            replaced += dir_replaced;
+            removed += dir_removed;
+            continue;
+        }
+        let unchanged = target.is_file()
+            && fs::metadata(&source)?.len() == fs::metadata(&target)?.len()
+            && manifest::sha256_file(&source)? == manifest::sha256_file(&target)?;
+        if unchanged {
+            continue;
+        }
+        debug!("Replacing {:?}", target);
+        if target.is_dir() {
+            fs::remove_dir_all(&target)?;
+        }
+        // The temporary folder may be on another drive than Godot's data folder.
+        if fs::rename(&source, &target).is_err() {
+            fs::copy(&source, &target).with_context(|| format!("Failed to write {:?}", target))?;
+        }
+        replaced += 1;
+    }
+    for entry in fs::read_dir(installed)? {
+        let entry = entry?;
+        if extracted.join(entry.file_name()).exists() {
+            continue;
+        }
+        debug!("Removing {:?}, the new templates don't have it", entry.path());
+        if entry.path().is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+        removed += 1;
+    }
+    Ok((replaced, removed))
+}
+
+/// Desktop templates every .tpz ships, used to tell a complete install from a broken one.
+fn missing_template_files(dir: &Path) -> Vec<&'static str> {
+    [Platform::Windows, Platform::Linux, Platform::Macos]
+        .iter()
+        .flat_map(|platform| [platform.release_template(), platform.debug_template()])
+        .filter(|file| !dir.join(file).exists())
+        .fold(Vec::new(), |mut missing, file| {
+            // macOS uses the same archive for both.
+            if !missing.contains(&file) {
+                missing.push(file);
+            }
+            missing
+        })
+}
+
+fn list_installed(root: &Path, config: &Config) -> Result<()> {
+    let versions = godot::installed_versions(root, config)?;
+    if versions.is_empty() {
+        info!("No Godot version installed in {:?}. Run 'cargo xtask setup'.", config.godot.install_root(root));
+        return Ok(());
+    }
+
+    let template_dir = get_godot_templates_dir()?;
+    for version in versions {
+        let mut godot = config.godot.clone();
+        godot.version = version.clone();
+        let current = if version == config.godot.version { "*" } else { " " };
+        let has_templates = template_dir.join(godot.templates_version()).exists();
+        if report::json() {
+            let current = version == config.godot.version;
+            report::event("installed", json!({ "version": version, "current": current, "templates": has_templates }));
+            continue;
+        }
+        let templates = if has_templates { "" } else { " (no export templates)" };
+        println!("{current} {version}{templates}");
+    }
+
+    Ok(())
+}
+
+/// For the `version` command. Nothing in it fails: what can't be found is reported as such.
+fn print_versions(root: &Path, config: &Config) {
+    let installed = godot::godot_executable(root, config)
+        .and_then(|godot| godot::reported_version(&godot).map(|version| (godot, version)));
+    let gdext = metadata::locked_versions(root).remove("godot");
+    let host = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+
+    if report::json() {
+        let (godot_path, godot_version) = match &installed {
+            Ok((path, version)) => (Some(path), Some(version)),
+            Err(_) => (None, None),
+        };
+        report::event(
+            "version",
+            json!({
+                "xtask": env!("CARGO_PKG_VERSION"),
+                "godot_configured": config.godot.version,
+                "godot_installed": godot_version,
+                "godot_path": godot_path,
+                "gdext": gdext,
+                "host": host,
+            }),
+        );
+        return;
+    }
+
+    println!("xtask:   {}", env!("CARGO_PKG_VERSION"));
+    println!("Godot:   {} (configured{})", config.godot.version, if config.godot.mono { ", .NET" } else { "" });
+    match &installed {
+        Ok((path, version)) => println!("Editor:  {} ({})", version, path.display()),
+        Err(err) => println!("Editor:  not found ({:#})", err),
+    }
+    println!("gdext:   {}", gdext.as_deref().unwrap_or("not in Cargo.lock"));
+    println!("Host:    {}", host);
+}
+
+/// Prints the export templates folder Godot reads, the versions in it and, for the configured
+/// version, the template files of every platform.
+fn list_templates(config: &Config) -> Result<()> {
+    let template_dir = get_godot_templates_dir()?;
+    let configured = config.godot.templates_version();
+    let url = templates_url(&config.godot);
+    let mut versions: Vec<String> = match fs::read_dir(&template_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    versions.sort();
+
+    let version_dir = template_dir.join(&configured);
+    let mut files: Vec<String> = match fs::read_dir(&version_dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.file_name().to_string_lossy().into_owned()).collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    let platforms = [Platform::Windows, Platform::Linux, Platform::Macos, Platform::Android, Platform::Web];
+    // Godot names its templates after the platform, e.g. linux_release.x86_64 or macos.zip.
+    let platform_files = |platform: Platform| -> Vec<&String> {
+        files.iter().filter(|file| file.starts_with(platform.dir_name())).collect()
+    };
+
+    if report::json() {
+        let by_platform: serde_json::Map<String, serde_json::Value> = platforms
+            .iter()
+            .map(|platform| (platform.dir_name().to_string(), json!(platform_files(*platform))))
+            .collect();
+        report::event(
+            "templates",
+            json!({
+                "dir": template_dir,
+                "versions": versions,
+                "configured": configured,
+                "installed": versions.contains(&configured),
+                "url": url,
+                "files": by_platform,
+            }),
+        );
+        return Ok(());
+    }
+
+    println!("Export templates folder: {}", template_dir.display());
+    if versions.is_empty() {
+        println!("  (no versions installed)");
+    }
+    for version in &versions {
+        let current = if *version == configured { "*" } else { " " };
+        println!("{current} {version}");
+    }
+    println!();
+    if !versions.contains(&configured) {
+        println!("Godot {} needs {}, which isn't installed. Run 'cargo xtask setup', or download", config.godot.version, configured);
+        println!("{} and install it from the editor (Editor > Manage Export Templates).", url);
+        return Ok(());
+    }
+    println!("Templates of {} ({}):", configured, url);
+    for platform in platforms {
+        let found = platform_files(platform);
+        let required = [platform.release_template(), platform.debug_template()];
+        let mut missing: Vec<&str> = required.iter().copied().filter(|file| !version_dir.join(file).exists()).collect();
+        // macOS uses the same archive for both.
+        missing.dedup();
+        let status = if missing.is_empty() { String::new() } else { format!("  (missing {})", missing.join(", ")) };
+        let names = if found.is_empty() { "-".to_string() } else { found.iter().map(|file| file.as_str()).collect::<Vec<_>>().join(", ") };
+        println!("  {:8} {}{}", platform.dir_name(), names, status);
+    }
+    Ok(())
+}
+
+fn uninstall(root: &Path, config: &Config, editor: bool, templates: bool, yes: bool) -> Result<()> {
+    let mut targets = Vec::new();
+    if editor {
+        targets.push(config.godot.bin_dir(root));
+    }
+    if templates {
+        targets.push(get_godot_templates_dir()?.join(config.godot.templates_version()));
+    }
+    targets.retain(|path| path.exists());
+
+    if targets.is_empty() {
+        info!("Nothing to remove for Godot {}", config.godot.version);
+        return Ok(());
+    }
+
+    for path in &targets {
+        info!("Will remove {:?}", path);
+    }
+    // A dry run removes nothing, so there's nothing to confirm either.
+    if !yes && !DRY_RUN.load(Ordering::Relaxed) && !confirm("Remove these folders?")? {
+        anyhow::bail!("Nothing removed. Pass --yes to uninstall without a prompt.");
+    }
+
+    for path in targets {
+        if !dry_run(format_args!("remove {:?}", path)) {
+            fs::remove_dir_all(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+            info!("Removed {:?}", path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the GDExtension crates, i.e. the workspace crates built as a `cdylib`,
+/// optionally restricted to the `selected` ones.
+fn extension_crates(metadata: &Metadata, selected: &[String]) -> Result<Vec<ExtensionCrate>> {
+    let crates = metadata.cdylib_crates();
+
+    if crates.is_empty() {
+        anyhow::bail!(
+            "No cdylib crate found in the workspace. Add `crate-type = [\"cdylib\"]` to the [lib] section of your extension crate."
+        );
+    }
+
+    let selected_crates = if selected.is_empty() {
+        crates
+    } else {
+        let available = crates.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ");
+        let mut remaining = crates;
+        let mut selected_crates = Vec::new();
+        for name in selected {
+            let Some(index) = remaining.iter().position(|c| c.name == *name || c.package == *name) else {
+                // Without the cdylib crate type cargo builds fine but produces no library to copy.
+                if metadata.has_package(name) {
+                    anyhow::bail!(
+                        "Crate '{}' is not built as a cdylib. Add `crate-type = [\"cdylib\"]` to the [lib] section of its Cargo.toml.",
+                        name
+                    );
+                }
+                anyhow::bail!("No cdylib crate named '{}' (available: {})", name, available);
+            };
+            selected_crates.push(remaining.remove(index));
+        }
+        selected_crates
+    };
+
+    if let Some(extension) = selected_crates.iter().find(|c| !c.uses_gdext) {
+        anyhow::bail!(
+            "Crate '{}' doesn't depend on gdext. Add `godot = {{ git = \"https://github.com/godot-rust/gdext\", branch = \"master\" }}` to its [dependencies].",
+            extension.package
+        );
+    }
+
+    Ok(selected_crates)
+}
+
+/// Crates picked with `--crate`, falling back to `build.crates` from xtask.toml.
+fn selected_crates(config: &Config, args: &BuildArgs) -> Vec<String> {
+    match &args.crate_name {
+        Some(crate_name) => vec![crate_name.clone()],
+        None => config.build.crates.clone(),
+    }
+}
+
+/// `metadata` is loaded once by the command and shared by every step that needs it, since
+/// `cargo metadata` takes a noticeable moment on every call.
+fn build_and_install(root: &Path, config: &Config, metadata: &Metadata, release: bool, args: &BuildArgs) -> Result<()> {
+    // Once is enough, watch builds over and over.
+    static GDEXT_CHECK: std::sync::Once = std::sync::Once::new();
+    GDEXT_CHECK.call_once(|| compat::check_gdext_version(metadata, &config.godot));
+
+    hooks::run_hooks(root, "pre_build", &config.hooks.pre_build)?;
+    build_crates(root, config, metadata, release, args)?;
+    hooks::run_hooks(root, "post_build", &config.hooks.post_build)
+}
+
+/// For `--no-build`: makes sure the libraries a build would install are already in game/bin.
+fn check_installed(root: &Path, config: &Config, metadata: &Metadata, release: bool, args: &BuildArgs) -> Result<()> {
+    let (_, libraries) = installed_libraries(root, config, metadata, release, args)?;
+    for (name, artifact) in &libraries {
+        if !artifact.exists() {
+            anyhow::bail!(
+                "--no-build: no {} build of {} at {:?}. Run the command once without --no-build.",
+                profile_dir(args.release_variant(release)),
+                name,
+                artifact
+            );
+        }
+        debug!("Using the installed {:?}", artifact);
+    }
+    Ok(())
+}
+
+/// Where a build installs each extension's library in game/bin, by library name, and the
+/// platform they're for. Only the first `--target` counts.
+fn installed_libraries(
+    root: &Path,
+    config: &Config,
+    metadata: &Metadata,
+    release: bool,
+    args: &BuildArgs,
+) -> Result<(Platform, Vec<(String, PathBuf)>)> {
+    let release = args.release_variant(release);
+    let crates = extension_crates(metadata, &selected_crates(config, args))?;
+    let bin_dir = config.game_dir(root).join(args.extension_settings(config)?.bin_dir());
+    let (platform, arch) = match args.targets.first() {
+        Some(triple) => platform::parse_triple(triple)
+            .with_context(|| format!("Unsupported target triple: {}", triple))?,
+        None => platform::default_target(),
+    };
+    let arch_dir = if args.universal { "universal" } else { arch.tag() };
+
+    let libraries = crates
+        .iter()
+        .map(|extension| {
+            let library_name = args.library_name(extension);
+            let artifact = bin_dir
+                .join(&library_name)
+                .join(platform.dir_name())
+                .join(arch_dir)
+                .join(profile_dir(release))
+                .join(platform.library_file_name(&library_name));
+            (library_name, artifact)
+        })
+        .collect();
+    Ok((platform, libraries))
+}
+
+/// Before `run` and `editor`: warns when the libraries are built for another CPU than the
+/// editor that's about to load them.
+fn check_editor_arch(root: &Path, config: &Config, metadata: &Metadata, args: &BuildArgs) -> Result<()> {
+    let (platform, libraries) = installed_libraries(root, config, metadata, false, args)?;
+    if platform != Platform::host() {
+        return Ok(());
+    }
+    // Without an editor run_godot fails with a better message.
+    let Ok(editor) = godot::godot_executable(root, config) else {
+        return Ok(());
+    };
+    binary_arch::check_editor_arch(&editor, platform, &libraries);
+    Ok(())
+}
+
+/// Builds the extension crates and installs them with their .gdextension files.
+fn build_crates(root: &Path, config: &Config, metadata: &Metadata, release: bool, args: &BuildArgs) -> Result<()> {
+    let release = args.release_variant(release);
+    let game_dir = config.game_dir(root);
+    let extension_settings = args.extension_settings(config)?;
+    build_info::write_build_info(root, &game_dir)?;
+    assets::sync_assets(root, &game_dir, &config.assets)?;
+    let selected = selected_crates(config, args);
+    let crates = extension_crates(metadata, &selected)?;
+    // Only narrow the cargo build when specific crates were asked for.
+    let packages: Vec<&str> = if selected.is_empty() {
+        Vec::new()
+    } else {
+        crates.iter().map(|c| c.package.as_str()).collect()
+    };
+
+    if args.universal {
+        if Platform::host() != Platform::Macos {
+            anyhow::bail!("--universal is only supported when building on macOS");
+        }
+
+        let arm64 = cargo_build(root, metadata, release, Some("aarch64-apple-darwin"), &packages, args)?;
+        let x86_64 = cargo_build(root, metadata, release, Some("x86_64-apple-darwin"), &packages, args)?;
+
+        for extension in &crates {
+            let file_name = Platform::Macos.library_file_name(&extension.name);
+            let library_name = args.library_name(extension);
+            let output_dir = game_dir
+                .join(extension_settings.bin_dir())
+                .join(&library_name)
+                .join("macos/universal")
+                .join(profile_dir(release));
+            let dst = output_dir.join(Platform::Macos.library_file_name(&library_name));
+
+            let mut cmd = Command::new("lipo");
+            cmd.arg("-create")
+                .arg(arm64.join(&file_name))
+                .arg(x86_64.join(&file_name))
+                .arg("-output")
+                .arg(&dst);
+            if !dry_run(format_args!("run {:?}", cmd)) {
+                fs::create_dir_all(&output_dir)?;
+                trace_command(&cmd);
+                let status = cmd.status().context("Failed to run lipo")?;
+                if !status.success() {
+                    anyhow::bail!("lipo failed to create the universal dylib for {}", extension.name);
+                }
+                info!("Created universal artifact at {:?}", dst);
+                if args.should_strip(release) {
+                    strip_artifact(&arm64.join(&file_name), &dst, Platform::Macos, args.dsym)?;
+                }
+                if !args.keep_install_name {
+                    fix_install_name(&dst)?;
+                }
+            }
+
+            generate_gdextension_file(&game_dir, &extension.name, &extension_settings, true)?;
+        }
+
+        return Ok(());
+    }
+
+    if args.targets.len() > 1 {
+        return build_targets_in_parallel(root, metadata, &game_dir, &crates, &packages, release, args, &extension_settings);
+    }
+
+    let target = args.targets.first().map(String::as_str);
+    let (platform, arch) = match target {
+        Some(triple) => platform::parse_triple(triple)
+            .with_context(|| format!("Unsupported target triple: {}", triple))?,
+        None => platform::default_target(),
+    };
+    if platform == Platform::Macos {
+        macos::check_native_arch(arch);
+    }
+    check_target_platform(platform, &crates)?;
+
+    let target_dir = cargo_build(root, metadata, release, target, &packages, args)?;
+    install_crates(&game_dir, &crates, &target_dir, platform, arch, release, args, &extension_settings)
+}
+
+/// Fails before building for a platform the crates can't be installed for.
+fn check_target_platform(platform: Platform, crates: &[ExtensionCrate]) -> Result<()> {
+    if platform != Platform::Ios {
+        return Ok(());
+    }
+    if Platform::host() != Platform::Macos {
+        anyhow::bail!("Building for iOS needs macOS with Xcode installed");
+    }
+    if let Some(extension) = crates.iter().find(|extension| !extension.staticlib) {
+        anyhow::bail!(
+            "Crate '{}' isn't built as a staticlib, which iOS needs. Change its [lib] section to `crate-type = [\"cdylib\", \"staticlib\"]`.",
+            extension.package
+        );
+    }
+    Ok(())
+}
+
+/// Most cargo builds running at once, since each one already uses every core for its own crates.
+const MAX_PARALLEL_BUILDS: usize = 4;
+
+/// Builds every `--target` concurrently and installs the ones that succeeded.
+///
+/// A failing target doesn't stop the others; the results are reported together at the end.
+#[allow(clippy::too_many_arguments)]
+fn build_targets_in_parallel(
+    root: &Path,
+    metadata: &Metadata,
+    game_dir: &Path,
+    crates: &[ExtensionCrate],
+    packages: &[&str],
+    release: bool,
+    args: &BuildArgs,
+    extension_settings: &ExtensionConfig,
+) -> Result<()> {
+    // Reject unknown triples before starting any build.
+    let mut resolved = Vec::new();
+    for triple in &args.targets {
+        let (platform, arch) = platform::parse_triple(triple)
+            .with_context(|| format!("Unsupported target triple: {}", triple))?;
+        check_target_platform(platform, crates)?;
+        resolved.push((triple.as_str(), platform, arch));
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = std::sync::Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..MAX_PARALLEL_BUILDS.min(resolved.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(&(triple, _, _)) = resolved.get(index) else {
+                    break;
+                };
+                let result = cargo_build(root, metadata, release, Some(triple), packages, args);
+                results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+
+    // Installing touches the shared .gdextension files, so it happens sequentially.
+    let mut failed = Vec::new();
+    info!("Build summary:");
+    for (index, result) in results {
+        let (triple, platform, arch) = resolved[index];
+        let result = result.and_then(|target_dir| {
+            install_crates(game_dir, crates, &target_dir, platform, arch, release, args, extension_settings)
+        });
+        match result {
+            Ok(()) => info!("  {triple}: ok"),
+            Err(err) => {
+                error!("  {triple}: failed ({err:#})");
+                failed.push(triple);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("{} of {} targets failed: {}", failed.len(), resolved.len(), failed.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Copies the libraries built for one platform and architecture into game/bin and
+/// regenerates their .gdextension files.
+#[allow(clippy::too_many_arguments)]
+fn install_crates(
+    game_dir: &Path,
+    crates: &[ExtensionCrate],
+    target_dir: &Path,
+    platform: Platform,
+    arch: Arch,
+    release: bool,
+    args: &BuildArgs,
+    extension_settings: &ExtensionConfig,
+) -> Result<()> {
+    for extension in crates {
+        // Move Artifacts into an arch and profile specific folder, e.g. bin/game/linux/arm64/release
+        let library_name = args.library_name(extension);
+        let output_dir = game_dir
+            .join(extension_settings.bin_dir())
+            .join(&library_name)
+            .join(platform.dir_name())
+            .join(arch.tag())
+            .join(profile_dir(release));
+
+        let file_name = platform.library_file_name(&extension.name);
+        let installed_name = platform.library_file_name(&library_name);
+        report::timed_detail(&format!("copy {}", installed_name), || {
+            install_artifact(target_dir, &output_dir, &file_name, &installed_name, platform, args, release)
+        })?;
+        // Generate the configuration
+        report::timed_detail(&format!("generate {}.gdextension", extension.name), || {
+            generate_gdextension_file(game_dir, &extension.name, extension_settings, false)
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+/// Needs Developer Mode or admin rights on Windows, which is why callers fall back to copying.
+#[cfg(windows)]
+fn symlink_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(src, dst)
+}
+
+fn find_sccache() -> Option<PathBuf> {
+    let name = format!("sccache{}", std::env::consts::EXE_SUFFIX);
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).map(|dir| dir.join(&name)).find(|candidate| candidate.is_file())
+}
+
+/// Shows sccache's hit rate with `-v`.
+fn print_sccache_stats() {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+    let mut cmd = Command::new("sccache");
+    cmd.arg("--show-stats");
+    trace_command(&cmd);
+    match cmd.output() {
+        Ok(output) if output.status.success() => debug!("{}", String::from_utf8_lossy(&output.stdout).trim_end()),
+        _ => debug!("Failed to read the sccache statistics"),
+    }
+}
+
+/// Cargo's output folder for the profile, which is also the bin sub-folder it's installed into.
+fn profile_dir(release: bool) -> &'static str {
+    if release { "release" } else { "debug" }
+}
+
+/// Copies a built library from the cargo target dir into the project's bin folder.
+///
+/// The copy is skipped when the destination already matches, since every write makes
+/// Godot reload the extension.
+fn install_artifact(
+    target_dir: &Path,
+    output_dir: &Path,
+    file_name: &str,
+    installed_name: &str,
+    platform: Platform,
+    args: &BuildArgs,
+    release: bool,
+) -> Result<()> {
+    let src = target_dir.join(file_name);
+    let dst = output_dir.join(installed_name);
+
+    // Nothing was built, so there's nothing to compare either.
+    if args.link && dry_run(format_args!("link {:?} to {:?}", dst, src)) {
+        return Ok(());
+    }
+    let and_strip = if args.should_strip(release) { " and strip it" } else { "" };
+    if dry_run(format_args!("copy {:?} to {:?}{}", src, dst, and_strip)) {
+        return Ok(());
+    }
+
+    if !output_dir.exists() {
+        fs::create_dir_all(output_dir)?;
+    }
+
+    if !src.exists() {
+        anyhow::bail!("Failed to find artifact: {:?}", src);
+    }
+
+    let existing_link = fs::symlink_metadata(&dst).is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if args.link {
+        if existing_link && fs::read_link(&dst).is_ok_and(|target| target == src) {
+            debug!("Artifact already linked: {:?}", dst);
+            return Ok(());
+        }
+        if dst.exists() || existing_link {
+            fs::remove_file(&dst)?;
+        }
+        match symlink_file(&src, &dst) {
+            // The link points into target/, so there's nothing to strip or rename; it's the cargo output itself.
+            Ok(()) => {
+                info!("Linked artifact {:?} -> {:?}", dst, src);
+                return Ok(());
+            }
+            Err(err) => warn!("Failed to symlink {:?} ({}), copying it instead", dst, err),
+        }
+    } else if existing_link {
+        // A link from an earlier --link build would make the comparison below look at the cargo output.
+        fs::remove_file(&dst)?;
+    }
+
+    let strip = args.should_strip(release);
+    if !args.force && is_same_file_version(&src, &dst, strip)? {
+        debug!("Artifact unchanged: {:?}", dst);
+        return Ok(());
+    }
+
+    fs::copy(&src, &dst)?;
+    if strip {
+        strip_artifact(&src, &dst, platform, args.dsym)?;
+    }
+    if platform == Platform::Macos && Platform::host() == Platform::Macos && !args.keep_install_name {
+        fix_install_name(&dst)?;
+    }
+    // Carry the build time over so the next comparison can tell the files apart.
+    let modified = fs::metadata(&src)?.modified()?;
+    fs::File::options().write(true).open(&dst)?.set_modified(modified)?;
+    // Shown so the effect of --lto, --opt-level and stripping can be compared.
+    let size = fs::metadata(&dst)?.len();
+    info!("Copied artifact to {:?} ({})", dst, format_size(size));
+    report::event("artifact", json!({ "path": dst, "size": size }));
+
+    // MSVC writes the debug info to a separate .pdb, which crash dumps need to be symbolicated.
+    // It's left out of release builds unless asked for, so it doesn't end up in distributions.
+    if platform == Platform::Windows && (!release || args.with_symbols) {
+        let pdb_name = Path::new(file_name).with_extension("pdb");
+        let pdb = target_dir.join(&pdb_name);
+        if pdb.exists() {
+            fs::copy(&pdb, output_dir.join(&pdb_name))?;
+            info!("Copied debug symbols to {:?}", output_dir.join(&pdb_name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `dst` is a copy of the current `src`, judging by size and modification time.
+/// A stripped copy is smaller than its source, so only the time is compared then.
+fn is_same_file_version(src: &Path, dst: &Path, stripped: bool) -> Result<bool> {
+    if !dst.exists() {
+        return Ok(false);
+    }
+
+    let src_meta = fs::metadata(src)?;
+    let dst_meta = fs::metadata(dst)?;
+    let same_size = stripped || src_meta.len() == dst_meta.len();
+    Ok(same_size && src_meta.modified()? == dst_meta.modified()?)
+}
+
+/// Removes debug info from the installed copy of a library, leaving the cargo artifact intact.
+fn strip_artifact(src: &Path, dst: &Path, platform: Platform, dsym: bool) -> Result<()> {
+    let mut cmd = match platform {
+        Platform::Linux => {
+            let mut cmd = Command::new("strip");
+            cmd.arg("--strip-debug");
+            cmd
+        }
+        Platform::Android => {
+            // The host's strip may not understand the other architectures, the NDK's llvm-strip does.
+            let llvm_strip = format!("llvm-strip{}", std::env::consts::EXE_SUFFIX);
+            let mut cmd = Command::new(android_ndk_toolchain().map(|bin| bin.join(&llvm_strip)).unwrap_or(llvm_strip.into()));
+            cmd.arg("--strip-debug");
+            cmd
+        }
+        Platform::Macos => {
+            if dsym {
+                let dsym_path = PathBuf::from(format!("{}.dSYM", src.display()));
+                let mut dsymutil = Command::new("dsymutil");
+                dsymutil.arg(src).arg("-o").arg(&dsym_path);
+                trace_command(&dsymutil);
+                match dsymutil.status() {
+                    Ok(status) if status.success() => info!("Wrote debug symbols to {:?}", dsym_path),
+                    _ => warn!("dsymutil failed, no .dSYM was produced"),
+                }
+            }
+            let mut cmd = Command::new("strip");
+            cmd.arg("-S");
+            cmd
+        }
+        // MSVC keeps debug info in the separate .pdb, wasm side modules are left alone, and
+        // Xcode strips the iOS app once it has linked the static library in.
+        Platform::Windows | Platform::Web | Platform::Ios => return Ok(()),
+    };
+
+    let before = fs::metadata(dst)?.len();
+    cmd.arg(dst);
+    trace_command(&cmd);
+    match cmd.status() {
+        Ok(status) if status.success() => {
+            let after = fs::metadata(dst)?.len();
+            info!("Stripped {:?}: {} -> {}", dst, format_size(before), format_size(after));
+        }
+        _ => warn!("failed to strip {:?}, keeping debug symbols", dst),
+    }
+
+    Ok(())
+}
+
+/// Makes a copied dylib relocatable by replacing its install name, which the linker sets to
+/// an absolute path inside the cargo target dir, with `@rpath/<file>`. Depending on the linker
+/// settings Godot otherwise fails to load the library once it lives under res://bin.
+fn fix_install_name(dylib: &Path) -> Result<()> {
+    let file_name = dylib.file_name().context("dylib path has no file name")?.to_string_lossy();
+
+    let mut cmd = Command::new("install_name_tool");
+    cmd.arg("-id").arg(format!("@rpath/{file_name}")).arg(dylib);
+    trace_command(&cmd);
+    match cmd.status() {
+        Ok(status) if status.success() => {}
+        _ => {
+            warn!("install_name_tool failed on {:?}, keeping its install name", dylib);
+            return Ok(());
+        }
+    }
+
+    // Editing the binary invalidates its signature, and arm64 refuses to load unsigned code.
+    let mut codesign = Command::new("codesign");
+    codesign.arg("--force").arg("--sign").arg("-").arg(dylib);
+    trace_command(&codesign);
+    match codesign.status() {
+        Ok(status) if status.success() => debug!("Set install name of {:?} to @rpath/{}", dylib, file_name),
+        _ => warn!("failed to re-sign {:?} after changing its install name", dylib),
+    }
+
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MB {
+        format!("{:.1} MB", bytes as f64 / MB)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// Runs `cargo build` and returns the directory the artifacts were written to.
+fn cargo_build(root: &Path, metadata: &Metadata, release: bool, target: Option<&str>, packages: &[&str], args: &BuildArgs) -> Result<PathBuf> {
+    info!("Building Rust crates{}...", target.map(|t| format!(" for {}", t)).unwrap_or_default());
+
+    let mut cmd = Command::new("cargo");
+    cmd.arg("build");
+    args.apply_cargo_profile(&mut cmd, release);
+    if let Some(target) = target {
+        cmd.arg("--target").arg(target);
+    }
+    for package in packages {
+        cmd.arg("-p").arg(package);
+    }
+    args.apply_features(&mut cmd);
+    args.apply_jobs(&mut cmd);
+    args.apply_profile(&mut cmd, release);
+    let sccache = args.apply_sccache(&mut cmd);
+    if offline() {
+        cmd.arg("--offline");
+    }
+
+    if !dry_run(format_args!("run {:?}", cmd)) {
+        trace_command(&cmd);
+        let step = format!("cargo build{}", target.map(|t| format!(" --target {}", t)).unwrap_or_default());
+        let status = report::timed_detail(&step, || interrupt::status(&mut cmd))?;
+        if !status.success() {
+            anyhow::bail!("Cargo build failed");
+        }
+        if sccache {
+            print_sccache_stats();
+        }
+    }
+
+    Ok(artifact_dir(metadata, target, args.cargo_profile(release)))
+}
+
+/// Folder cargo writes a build's libraries to: under the target dir cargo reports, which follows
+/// CARGO_TARGET_DIR, and for cross builds in target/<triple>/<profile>.
+fn artifact_dir(metadata: &Metadata, target: Option<&str>, profile: &str) -> PathBuf {
+    let mut target_dir = metadata.target_directory.clone();
+    if let Some(target) = target {
+        target_dir.push(target);
+    }
+    target_dir.push(cargo_output_dir(profile));
+    target_dir
+}
+
+/// Folder under target/ cargo writes a profile's artifacts to.
+fn cargo_output_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        custom => custom,
+    }
+}
+
+fn build_web_and_install(root: &Path, config: &Config, metadata: &Metadata, release: bool, args: &BuildArgs) -> Result<()> {
+    const WEB_TARGET: &str = "wasm32-unknown-emscripten";
+    let release = args.release_variant(release);
+
+    let crates = extension_crates(metadata, &selected_crates(config, args))?;
+    let bin_dir = config.game_dir(root).join(args.extension_settings(config)?.bin_dir());
+
+    info!("Building Rust crates for {}...", WEB_TARGET);
+
+    // The web export runs with threads, so the std library has to be rebuilt with atomics,
+    // which requires nightly. The extension is loaded as an emscripten side module.
+    let mut cmd = Command::new("cargo");
+    cmd.arg("+nightly")
+        .arg("build")
+        .arg("-Zbuild-std")
+        .arg("--target")
+        .arg(WEB_TARGET)
+        .env(
+            "RUSTFLAGS",
+            "-C link-args=-pthread -C target-feature=+atomics -C link-args=-sSIDE_MODULE=2 \
+             -Zlink-native-libraries=no -Cllvm-args=-enable-emscripten-cxx-exceptions=0",
+        );
+    args.apply_cargo_profile(&mut cmd, release);
+    // Only the extensions can be built for the web, not the rest of the workspace.
+    for extension in &crates {
+        cmd.arg("-p").arg(&extension.package);
+    }
+    args.apply_features(&mut cmd);
+    args.apply_jobs(&mut cmd);
+    args.apply_profile(&mut cmd, release);
+    let sccache = args.apply_sccache(&mut cmd);
+    if offline() {
+        cmd.arg("--offline");
+    }
+
+    if !dry_run(format_args!("run {:?}", cmd)) {
+        trace_command(&cmd);
+        let status = interrupt::status(&mut cmd)?;
+        if !status.success() {
+            anyhow::bail!("Cargo build for {} failed", WEB_TARGET);
+        }
+        if sccache {
+            print_sccache_stats();
+        }
+    }
+
+    let target_dir = artifact_dir(metadata, Some(WEB_TARGET), args.cargo_profile(release));
+
+    for extension in &crates {
+        let library_name = args.library_name(extension);
+        let output_dir = bin_dir.join(&library_name).join("web").join(profile_dir(release));
+        let file_name = Platform::Web.library_file_name(&extension.name);
+        let installed_name = Platform::Web.library_file_name(&library_name);
+        install_artifact(&target_dir, &output_dir, &file_name, &installed_name, Platform::Web, args, release)?;
+    }
+
+    Ok(())
+}
+
+/// Rust targets of the ABIs in the Android export, with the prefix of their NDK clang.
+const ANDROID_TARGETS: [(&str, &str); 3] = [
+    ("aarch64-linux-android", "aarch64-linux-android"),
+    ("armv7-linux-androideabi", "armv7a-linux-androideabi"),
+    ("x86_64-linux-android", "x86_64-linux-android"),
+];
+
+/// API level the Android libraries are linked against, the lowest Godot 4 runs on.
+const ANDROID_API_LEVEL: u32 = 24;
+
+/// Builds the extensions for every Android ABI with the NDK's linkers and installs them into
+/// game/bin/<crate>/android/<arch>/.
+fn build_android_and_install(root: &Path, config: &Config, metadata: &Metadata, release: bool, args: &BuildArgs) -> Result<()> {
+    let toolchain = android_ndk_toolchain()?;
+
+    if let Some(installed) = installed_rust_targets() {
+        let missing: Vec<&str> = ANDROID_TARGETS
+            .iter()
+            .map(|(triple, _)| *triple)
+            .filter(|triple| !installed.iter().any(|target| target == triple))
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Rust targets for Android are missing: {}. Install them with 'rustup target add {}'.",
+                missing.join(", "),
+                missing.join(" ")
+            );
+        }
+    }
+
+    // A linker set in the environment or .cargo/config.toml wins over the NDK default.
+    let linker_suffix = if cfg!(target_os = "windows") { ".cmd" } else { "" };
+    for (triple, clang) in ANDROID_TARGETS {
+        let variable = format!("CARGO_TARGET_{}_LINKER", triple.to_uppercase().replace('-', "_"));
+        if std::env::var_os(&variable).is_some() {
+            continue;
+        }
+        let linker = toolchain.join(format!("{clang}{ANDROID_API_LEVEL}-clang{linker_suffix}"));
+        if !linker.exists() {
+            anyhow::bail!("The Android NDK has no linker for {} at {:?}", triple, linker);
+        }
+        // Set before any build starts, so every cargo process inherits it.
+        std::env::set_var(&variable, &linker);
+    }
+
+    let mut android = args.clone();
+    android.targets = ANDROID_TARGETS.iter().map(|(triple, _)| triple.to_string()).collect();
+    android.universal = false;
+    build_crates(root, config, metadata, release, &android)
+}
+
+/// The `bin` folder of the NDK's LLVM toolchain, from ANDROID_NDK_HOME, ANDROID_NDK_ROOT or the
+/// newest NDK installed in the Android SDK.
+fn android_ndk_toolchain() -> Result<PathBuf> {
+    let from_sdk = || {
+        let sdk = std::env::var_os("ANDROID_HOME").or_else(|| std::env::var_os("ANDROID_SDK_ROOT"))?;
+        let mut versions: Vec<PathBuf> = fs::read_dir(Path::new(&sdk).join("ndk"))
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        versions.sort();
+        versions.pop()
+    };
+    let ndk = std::env::var_os("ANDROID_NDK_HOME")
+        .or_else(|| std::env::var_os("ANDROID_NDK_ROOT"))
+        .map(PathBuf::from)
+        .or_else(from_sdk)
+        .context(
+            "Android NDK not found. Install it with the SDK manager (sdkmanager \"ndk;<version>\") and set ANDROID_NDK_HOME.",
+        )?;
+
+    // The macOS NDK is universal but still lives in darwin-x86_64.
+    let host = if cfg!(target_os = "windows") {
+        "windows-x86_64"
+    } else if cfg!(target_os = "macos") {
+        "darwin-x86_64"
+    } else {
+        "linux-x86_64"
+    };
+    let toolchain = ndk.join("toolchains/llvm/prebuilt").join(host).join("bin");
+    if !toolchain.is_dir() {
+        anyhow::bail!("Android NDK at {:?} has no LLVM toolchain for {} (expected {:?})", ndk, host, toolchain);
+    }
+    Ok(toolchain)
+}
+
+/// Targets installed with rustup, or None when the toolchain isn't managed by rustup.
+fn installed_rust_targets() -> Option<Vec<String>> {
+    let mut cmd = Command::new("rustup");
+    cmd.arg("target").arg("list").arg("--installed");
+    trace_command(&cmd);
+    let output = cmd.output().ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).lines().map(|line| line.trim().to_string()).collect())
+}
+
+/// Runs Godot until it exits, or with `detach` starts it in the background and leaves it be.
+fn run_godot(
+    root: &Path,
+    config: &Config,
+    editor: bool,
+    scene: Option<&str>,
+    launch: &LaunchArgs,
+    game_args: &[String],
+    detach: bool,
+) -> Result<()> {
+    let mut cmd = godot_command(root, config, editor)?;
+    launch.apply(&mut cmd);
+
+    // A trailing scene path makes Godot run (or, with -e, open) that scene instead of the main one.
+    if let Some(scene) = scene {
+        if !scene.starts_with("res://") || !(scene.ends_with(".tscn") || scene.ends_with(".scn")) {
+            warn!("'{}' doesn't look like a scene path (expected res://....tscn or .scn)", scene);
+        }
+        cmd.arg(scene);
+    }
+
+    // Everything after Godot's own `--` separator is left alone by the engine and handed
+    // to the project as user arguments, so nothing here can be mistaken for `-e` or `--path`.
+    if !game_args.is_empty() {
+        cmd.arg("--").args(game_args);
+    }
+
+    if dry_run(format_args!("run {:?}", cmd)) {
+        return Ok(());
+    }
+
+    info!("Launching Godot...");
+    trace_command(&cmd);
+    if detach {
+        // Nothing of it ends up in the terminal afterwards. The child isn't waited on or killed,
+        // so it keeps running after xtask exits.
+        let child = cmd
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("Failed to launch Godot process")?;
+        info!("Godot is running in the background (PID {})", child.id());
+        report::event("detached", json!({ "pid": child.id() }));
+        return Ok(());
+    }
+    wait_for_godot(&mut cmd, "Godot")?;
+
+    Ok(())
+}
+
+/// Runs the test runner headlessly and returns its exit code.
+fn run_tests(root: &Path, config: &Config, script: Option<String>, scene: Option<String>) -> Result<i32> {
+    let mut cmd = godot_command(root, config, false)?;
+    cmd.arg("--headless").arg("--audio-driver").arg("Dummy");
+
+    // A scene given on the command line wins over everything, then the CLI script, then xtask.toml.
+    match (scene, script) {
+        (Some(scene), _) => cmd.arg(scene),
+        (None, Some(script)) => cmd.arg("-s").arg(script),
+        (None, None) => match &config.test.scene {
+            Some(scene) => cmd.arg(scene),
+            None => cmd.arg("-s").arg(&config.test.script),
+        },
+    };
+
+    if dry_run(format_args!("run {:?}", cmd)) {
+        return Ok(0);
+    }
+
+    info!("Running tests...");
+    trace_command(&cmd);
+    let status = wait_for_godot(&mut cmd, "The test run")?;
+
+    // Godot exits with whatever the runner passed to get_tree().quit(); a crash has no code.
+    let code = status.code().unwrap_or(1);
+    if code == 0 {
+        info!("Tests passed");
+    } else {
+        error!("Tests failed (exit code {})", code);
+    }
+
+    Ok(code)
+}
+
+/// Imports the project's assets headlessly, filling `.godot/imported`. On a fresh checkout
+/// nothing else does, and an export then fails or ships without the resources it couldn't load.
+fn import_assets(root: &Path, config: &Config) -> Result<()> {
+    let mut import = godot_command(root, config, false)?;
+    import
+        .arg("--headless")
+        .arg("--audio-driver").arg("Dummy")
+        .arg("--display-driver").arg("headless");
+    // --import waits until everything is imported, but only exists since Godot 4.2. Before it,
+    // the editor imports on startup and --quit stops it right after.
+    let version = config.godot.features_version();
+    let mut parts = version.split('.').map(|part| part.parse::<u32>().unwrap_or(0));
+    if (parts.next().unwrap_or(0), parts.next().unwrap_or(0)) >= (4, 2) {
+        import.arg("--import");
+    } else {
+        import.arg("--editor").arg("--quit");
+    }
+    if dry_run(format_args!("run {:?}", import)) {
+        return Ok(());
+    }
+    trace_command(&import);
+    let status_import = report::timed("import", || wait_for_godot(&mut import, "The import"))?;
+    if !status_import.success() {
+        anyhow::bail!("Godot Import step failed.");
+    }
+    Ok(())
+}
+
+/// For the `godot` command: Godot with `--path` set to the project and `args` after it. Returns
+/// its exit code, 1 when it was killed by a signal.
+fn run_godot_passthrough(root: &Path, config: &Config, args: &[String]) -> Result<i32> {
+    let mut cmd = godot_command(root, config, false)?;
+    cmd.args(args);
+    if dry_run(format_args!("run {:?}", cmd)) {
+        return Ok(0);
+    }
+    trace_command(&cmd);
+    let status = wait_for_godot(&mut cmd, "Godot")?;
+    let code = status.code().unwrap_or(1);
+    report::event("godot", json!({ "exit_code": code }));
+    Ok(code)
+}
+
+/// Warns when project.godot's `config/features` names another engine version than the one
+/// that's about to run it, which Godot only reports as a vague warning when it opens the
+/// project. The file is only changed with `update`.
+fn check_project_features(root: &Path, config: &Config, update: bool) -> Result<()> {
+    let game_dir = config.game_dir(root);
+    let Some(project_version) = godot::project_engine_version(&game_dir) else {
+        return Ok(());
+    };
+    let engine_version = config.godot.features_version();
+    if project_version == engine_version {
+        return Ok(());
+    }
+    let project_file = game_dir.join("project.godot");
+    if !update {
+        warn!(
+            "{:?} was made with Godot {}, but {} is configured. Pass --update-project-features to make it target {}.",
+            project_file, project_version, config.godot.version, engine_version
+        );
+        return Ok(());
+    }
+    if dry_run(format_args!("change config/features in {:?} from {} to {}", project_file, project_version, engine_version)) {
+        return Ok(());
+    }
+    godot::set_project_engine_version(&game_dir, &project_version, &engine_version)?;
+    info!("{:?} now targets Godot {} (was {})", project_file, engine_version, project_version);
+    Ok(())
+}
+
+/// Prepares the command launching Godot on the project, bootstrapping it if needed.
+fn godot_command(root: &Path, config: &Config, editor: bool) -> Result<Command> {
+    let godot_exe = godot::godot_executable(root, config)?;
+
+    let game_dir = config.game_dir(root);
+    bootstrap_project(&game_dir, config)?;
+
+    // Get Absolute Paths
+    // Not canonicalized: a Snap launcher is a symlink that only works under its own name.
+    let godot_exe_abs = std::path::absolute(&godot_exe)
+        .context("Failed to resolve Godot executable path")?;
+
+    // The project may only exist once bootstrap_project really ran.
+    let game_dir_abs = game_dir.canonicalize().unwrap_or(game_dir);
+
+    let mut cmd = Command::new(&godot_exe_abs);
+
+    // This makes Godot treat the project folder as the root, avoiding the "working directory" error.
+    cmd.current_dir(&game_dir_abs);
+
+    if editor {
+        cmd.arg("-e");
+    }
+
+    cmd.arg("--path").arg(&game_dir_abs);
+    if config.godot.verbose {
+        cmd.arg("--verbose");
+    }
+
+    Ok(cmd)
+}
+
+/// Creates the minimal files Godot needs to open `game_dir` as a project, never touching existing ones.
+fn bootstrap_project(game_dir: &Path, config: &Config) -> Result<()> {
+    // Auto-Create 'project.godot' if missing
+    // This prevents the "Project Manager" wizard from appearing and complaining.
+    // An existing one is the user's and is never rewritten.
+    let project_file = game_dir.join("project.godot");
+    if !project_file.exists() && !dry_run(format_args!("create a minimal {:?}", project_file)) {
+        fs::create_dir_all(game_dir)?;
+        info!("project.godot missing. Creating minimal project...");
+
+        // Minimal Godot 4.x config
+        let name = config.project.name.replace('\\', "\\\\").replace('"', "\\\"");
+        let icon = &config.project.icon;
+        let features_version = config.godot.features_version();
+
+        // A new project gets a main scene so 'run' has something to start. An existing main.tscn
+        // is used as it is.
+        let main_scene = game_dir.join("main.tscn");
+        if !main_scene.exists() {
+            info!("main.tscn missing. Creating an empty main scene...");
+            fs::write(&main_scene, "[gd_scene format=3]\n\n[node name=\"Main\" type=\"Node2D\"]\n")?;
+        }
+
+        let content = format!(r#"; Engine configuration file.
+config_version=5
+
+[application]
+config/name="{name}"
+run/main_scene="res://main.tscn"
+config/features=PackedStringArray("{features_version}", "Forward Plus")
+config/icon="{icon}"
+"#);
+        let mut file = fs::File::create(&project_file)?;
+        file.write_all(content.as_bytes())?;
+    }
+
+    // The generated project references the icon, so make sure it exists.
+    let icon_file = match config.project.icon.strip_prefix("res://") {
+        Some(relative) if relative.ends_with(".svg") => game_dir.join(relative),
+        _ => return Ok(()),
+    };
+    if !icon_file.exists() && !dry_run(format_args!("create a default icon at {:?}", icon_file)) {
+        info!("icon.svg missing. Creating default icon...");
+
+        let icon = r##"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="128"><rect width="124" height="124" x="2" y="2" fill="#363d52" stroke="#212532" stroke-width="4" rx="14"/><circle cx="64" cy="64" r="36" fill="#478cbf"/><circle cx="52" cy="58" r="8" fill="#fff"/><circle cx="76" cy="58" r="8" fill="#fff"/></svg>
+"##;
+        if let Some(parent) = icon_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&icon_file, icon)?;
+    }
+
+    Ok(())
+}
+
+/// Turns `package` into a dedicated server or client export: its presets, and the `server` or
+/// `client` feature of every extension crate that has one, so code only one side needs can be
+/// left out with `#[cfg(feature = "server")]`. Those crates install the build as
+/// `<crate>_server` or `<crate>_client`, and the .gdextension picks it by feature tag.
+fn enable_role_build(
+    config: &mut Config,
+    metadata: &Metadata,
+    platforms: &[Platform],
+    build: &mut BuildArgs,
+    role: Role,
+) -> Result<()> {
+    if role == Role::Server {
+        if platforms.len() != 1 {
+            anyhow::bail!("--server exports one platform at a time, pass a single --platform");
+        }
+        if !matches!(platforms[0], Platform::Linux | Platform::Windows | Platform::Macos) {
+            anyhow::bail!("Dedicated servers can only be exported for Linux, Windows and macOS");
+        }
+    }
+    config.export.role = role;
+    build.role = role;
+
+    let feature = role.name().unwrap_or_default();
+    let crates = extension_crates(metadata, &selected_crates(config, build))?;
+    let mut enabled = false;
+    for extension in crates.iter().filter(|extension| extension.features.iter().any(|declared| declared == feature)) {
+        if !build.all_features {
+            build.features.push(format!("{}/{}", extension.package, feature));
+        }
+        enabled = true;
+    }
+    if !enabled {
+        warn!(
+            "No extension crate has a `{feature}` feature, the {feature} uses the regular libraries. Add `{feature} = []` to [features] to build it separately."
+        );
+    }
+    Ok(())
+}
+
+/// Name of the preset a platform is exported with, the same one `ensure_export_presets` generates.
+fn preset_name(platform: Platform, export: &ExportConfig) -> String {
+    match export.role {
+        Role::Full => platform.export_name().to_string(),
+        Role::Server => format!("{} Server", platform.export_name()),
+        Role::Client => format!("{} Client", platform.export_name()),
+    }
+}
+
+/// The architecture of every Windows and Linux export that isn't x86_64: the one of its
+/// `--target`, or of the host when it's built without one. A preset exports one architecture,
+/// so a platform can't be packaged for several at once.
+fn export_archs(platforms: &[Platform], build: &BuildArgs) -> Result<Vec<(Platform, Arch)>> {
+    let mut archs = Vec::new();
+    let host = build.targets.is_empty().then(platform::default_target);
+    for platform in platforms.iter().filter(|platform| matches!(platform, Platform::Windows | Platform::Linux)) {
+        let targeted: Vec<Arch> = build
+            .targets
+            .iter()
+            .filter_map(|triple| platform::parse_triple(triple))
+            .filter(|(target, _)| target == platform)
+            .map(|(_, arch)| arch)
+            .collect();
+        let arch = match (targeted.as_slice(), host) {
+            ([], Some((host, arch))) if host == *platform => arch,
+            ([], _) => Arch::X86_64,
+            ([arch], _) => *arch,
+            _ => anyhow::bail!(
+                "--target builds {} for more than one architecture, but an export has only one. Package each one separately.",
+                platform.dir_name()
+            ),
+        };
+        if *platform == Platform::Windows && arch == Arch::Arm32 {
+            anyhow::bail!("Godot has no 32-bit ARM export templates for Windows, build for x86_64 or aarch64.");
+        }
+        if arch != Arch::X86_64 {
+            archs.push((*platform, arch));
+        }
+    }
+    Ok(archs)
+}
+
+/// Checks the `--custom-template` files and assigns each one to its platform.
+fn custom_templates(root: &Path, platforms: &[Platform], given: &[(Option<Platform>, PathBuf)]) -> Result<Vec<(Platform, PathBuf)>> {
+    let mut templates: Vec<(Platform, PathBuf)> = Vec::new();
+    for (platform, path) in given {
+        let platform = match (platform, platforms) {
+            (Some(platform), _) => *platform,
+            (None, [platform]) => *platform,
+            (None, _) => anyhow::bail!("--custom-template {:?}: say which platform it's for, e.g. linux={}", path, path.display()),
+        };
+        if !platforms.contains(&platform) {
+            anyhow::bail!("--custom-template: {} isn't one of the exported platforms", platform.dir_name());
+        }
+        if templates.iter().any(|(existing, _)| *existing == platform) {
+            anyhow::bail!("--custom-template: more than one template for {}", platform.dir_name());
+        }
+        let path = root.join(path);
+        let path = path.canonicalize().with_context(|| format!("--custom-template: {:?} doesn't exist", path))?;
+        check_custom_template(platform, &path)?;
+        templates.push((platform, path));
+    }
+    Ok(templates)
+}
+
+/// Godot only says the export failed when a template is the wrong kind of file, so that's
+/// checked before anything is built: an executable for the platform for Windows and Linux, the
+/// zip or apk Godot's build writes for the others.
+fn check_custom_template(platform: Platform, path: &Path) -> Result<()> {
+    if !path.is_file() {
+        anyhow::bail!("--custom-template: {:?} is not a file", path);
+    }
+    match platform {
+        Platform::Windows | Platform::Linux => {
+            if binary_arch::read_archs(path).is_none() {
+                anyhow::bail!("--custom-template: {:?} is not a {} executable", path, platform.export_name());
+            }
+            #[cfg(unix)]
+            if platform == Platform::Linux {
+                use std::os::unix::fs::PermissionsExt;
+                if fs::metadata(path)?.permissions().mode() & 0o111 == 0 {
+                    anyhow::bail!("--custom-template: {:?} is not executable, run chmod +x on it", path);
+                }
+            }
+        }
+        _ => {
+            let expected: &[&str] = if platform == Platform::Android { &["apk", "zip"] } else { &["zip"] };
+            let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+            if !expected.contains(&extension) {
+                anyhow::bail!("--custom-template: {:?} should be a .{} for {}", path, expected.join(" or ."), platform.export_name());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn ensure_export_presets(root: &Path, config: &Config, platforms: &[Platform], debug: bool) -> Result<()> {
+    let presets_path = config.game_dir(root).join("export_presets.cfg");
+    let existing = if presets_path.exists() { fs::read_to_string(&presets_path)? } else { String::new() };
+    let presets = existing_presets(&existing);
+    let custom_features = config.export.preset_features();
+    let encrypt = config.export.encrypt.to_string();
+
+    // Presets are looked up by name at export time, so that's what counts as present.
+    let mut missing = Vec::new();
+    // Existing presets are left alone, except for the options --embed-pck, --target and
+    // --custom-template ask to change.
+    let mut updated = existing.clone();
+    let mut changed = Vec::new();
+    for platform in platforms {
+        match presets.iter().find(|preset| preset.name == preset_name(*platform, &config.export)) {
+            Some(preset) => {
+                let before = updated.clone();
+                if let (Some(embed), Platform::Windows | Platform::Linux) = (config.export.embed_pck, platform) {
+                    let embedding = if embed { "Embedding" } else { "Not embedding" };
+                    debug!("{} the pack in the existing \"{}\" preset", embedding, preset.name);
+                    updated = set_preset_option(&updated, preset.index, "binary_format/embed_pck", &embed.to_string());
+                }
+                if !custom_features.is_empty() && preset.custom_features != custom_features {
+                    warn!(
+                        "The existing \"{}\" preset has custom_features=\"{}\", not \"{}\". Change it in the Godot editor (Project > Export > Features).",
+                        preset.name, preset.custom_features, custom_features
+                    );
+                }
+                if config.export.encrypt && preset.encrypt_pck != encrypt {
+                    warn!(
+                        "The existing \"{}\" preset doesn't encrypt the pack. Turn it on in the Godot editor (Project > Export > Encryption).",
+                        preset.name
+                    );
+                }
+                if let Some((_, arch)) = config.export.archs.iter().find(|(built, _)| built == platform) {
+                    debug!("Exporting the \"{}\" preset for {}", preset.name, arch.tag());
+                    updated = set_preset_option(&updated, preset.index, "binary_format/architecture", &format!("\"{}\"", arch.tag()));
+                }
+                if let Some(template) = config.export.custom_template(*platform) {
+                    debug!("Exporting the \"{}\" preset with {:?}", preset.name, template);
+                    updated = set_custom_template(&updated, preset.index, template, debug);
+                }
+                if updated != before {
+                    changed.push(format!("\"{}\"", preset.name));
+                }
+            }
+            None => missing.push(*platform),
+        }
+    }
+    if missing.is_empty() {
+        if !changed.is_empty() && !dry_run(format_args!("update the {} preset(s) in {:?}", changed.join(", "), presets_path)) {
+            fs::write(&presets_path, &updated)?;
+        }
+        return Ok(());
+    }
+
+    let builds_dir = project_relative(root, config, &config.export.builds_dir).display().to_string().replace('\\', "/");
+
+    // New presets continue the numbering, everything already in the file stays as it is.
+    let first_index = presets.iter().map(|preset| preset.index + 1).max().unwrap_or(0);
+    let added: String = missing
+        .iter()
+        .enumerate()
+        .map(|(offset, platform)| {
+            let section = preset_section(first_index + offset, *platform, &builds_dir, config);
+            match config.export.custom_template(*platform) {
+                Some(template) => set_custom_template(&section, first_index + offset, template, debug),
+                None => section,
+            }
+        })
+        .collect();
+
+    let names: Vec<String> = missing.iter().map(|platform| preset_name(*platform, &config.export)).collect();
+    if existing.is_empty() {
+        info!("Generating export_presets.cfg...");
+    } else {
+        info!("Adding {} to export_presets.cfg...", names.join(", "));
     }
 
+    let also_updated = if changed.is_empty() { String::new() } else { format!(" and update the {} preset(s)", changed.join(", ")) };
+    if !dry_run(format_args!("write {} to {:?}{}", names.join(", "), presets_path, also_updated)) {
+        let content = if existing.is_empty() {
+            added.trim().to_string()
+        } else {
+            format!("{}\n{}", updated.trim_end(), added.trim_end())
+        };
+        fs::write(&presets_path, content)?;
+    }
     Ok(())
 }
 
-fn get_platform_export_name() -> (&'static str, &'static str) {
-    if cfg!(target_os = "windows") {
-        ("Windows Desktop", ".exe")
+/// A `[preset.N]` section already in export_presets.cfg.
+struct ExistingPreset {
+    index: usize,
+    name: String,
+    platform: String,
+    export_path: String,
+    custom_features: String,
+    encrypt_pck: String,
+}
+
+/// Every `[preset.N]` section in an export_presets.cfg.
+fn existing_presets(content: &str) -> Vec<ExistingPreset> {
+    let mut presets: Vec<ExistingPreset> = Vec::new();
+    let mut in_preset = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            // "[preset.3]" opens a preset, "[preset.3.options]" and anything else doesn't.
+            let index = line
+                .strip_prefix("[preset.")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|index| index.parse::<usize>().ok());
+            in_preset = index.is_some();
+            if let Some(index) = index {
+                presets.push(ExistingPreset {
+                    index,
+                    name: String::new(),
+                    platform: String::new(),
+                    export_path: String::new(),
+                    custom_features: String::new(),
+                    encrypt_pck: String::new(),
+                });
+            }
+            continue;
+        }
+        let (Some(preset), true) = (presets.last_mut(), in_preset) else {
+            continue;
+        };
+        if let Some(name) = line.strip_prefix("name=") {
+            preset.name = name.trim_matches('"').to_string();
+        } else if let Some(platform) = line.strip_prefix("platform=") {
+            preset.platform = platform.trim_matches('"').to_string();
+        } else if let Some(export_path) = line.strip_prefix("export_path=") {
+            preset.export_path = export_path.trim_matches('"').to_string();
+        } else if let Some(features) = line.strip_prefix("custom_features=") {
+            preset.custom_features = features.trim_matches('"').to_string();
+        } else if let Some(encrypt) = line.strip_prefix("encrypt_pck=") {
+            preset.encrypt_pck = encrypt.to_string();
+        }
+    }
+    presets
+}
+
+/// `path` relative to the workspace root as seen from the project folder, e.g. "../builds"
+/// for `game/`. Absolute paths stay as they are, and so does anything when the project folder
+/// itself is outside the workspace.
+fn project_relative(root: &Path, config: &Config, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    let mut depth = 0;
+    for component in config.project.dir.components() {
+        match component {
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+            _ => return root.join(path),
+        }
+    }
+    let mut relative: PathBuf = std::iter::repeat_n("..", depth).collect();
+    relative.push(path);
+    relative
+}
+
+/// Points preset `index` at a template compiled from source, the debug or the release one.
+fn set_custom_template(content: &str, index: usize, template: &Path, debug: bool) -> String {
+    let key = if debug { "custom_template/debug" } else { "custom_template/release" };
+    let path = template.display().to_string().replace('\\', "/");
+    // Windows' canonical paths start with \\?\, which Godot can't open.
+    let path = path.strip_prefix("//?/").unwrap_or(&path);
+    set_preset_option(content, index, key, &format!("\"{}\"", path))
+}
+
+/// Sets `key` in the `[preset.N.options]` section of an export_presets.cfg and leaves every
+/// other line as it is. A key that isn't there yet is added at the top of the section, and a
+/// hand-edited preset without the section gets one.
+fn set_preset_option(content: &str, index: usize, key: &str, value: &str) -> String {
+    let header = format!("[preset.{index}.options]");
+    if !content.lines().any(|line| line.trim() == header) {
+        return match add_options_section(content, index) {
+            Some(added) => set_preset_option(&added, index, key, value),
+            None => content.to_string(),
+        };
+    }
+    let is_key = |line: &str| line.trim().strip_prefix(key).is_some_and(|rest| rest.starts_with('='));
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut result = String::with_capacity(content.len());
+    let mut in_options = false;
+    for (position, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_options = trimmed == header;
+            result.push_str(line);
+            let section = lines[position + 1..].iter().take_while(|line| !line.trim().starts_with('['));
+            if in_options && !section.copied().any(is_key) {
+                if !line.ends_with('\n') {
+                    result.push('\n');
+                }
+                result.push_str(&format!("{key}={value}\n"));
+            }
+            continue;
+        }
+        if in_options && is_key(line) {
+            let ending = &line[line.trim_end().len()..];
+            result.push_str(&format!("{key}={value}{ending}"));
+            continue;
+        }
+        result.push_str(line);
+    }
+    result
+}
+
+/// An empty `[preset.N.options]` right after the `[preset.N]` section, where Godot writes it.
+/// `None` when there's no such preset.
+fn add_options_section(content: &str, index: usize) -> Option<String> {
+    let preset = format!("[preset.{index}]");
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let start = lines.iter().position(|line| line.trim() == preset)?;
+    let end = lines[start + 1..].iter().position(|line| line.trim().starts_with('[')).map_or(lines.len(), |offset| start + 1 + offset);
+    let mut result: String = lines[..end].concat();
+    if !result.ends_with('\n') {
+        result.push('\n');
+    }
+    if !result.ends_with("\n\n") {
+        result.push('\n');
+    }
+    result.push_str(&format!("[preset.{index}.options]\n\n"));
+    result.push_str(&lines[end..].concat());
+    Some(result)
+}
+
+/// Where `package` writes an export, relative to the builds folder, e.g. "linux/game.x86_64".
+/// The generated presets point at the same file, so exporting from the editor lands there too.
+fn export_file(export: &ExportConfig, platform: Platform, debug: bool, pck_only: bool) -> PathBuf {
+    let platform_dir = match export.role {
+        Role::Full => platform.dir_name().to_string(),
+        Role::Server => "server".to_string(),
+        Role::Client => format!("{}-client", platform.dir_name()),
+    };
+    // Keep debug exports apart so they never clobber release artifacts.
+    let platform_dir = if debug { format!("{}-debug", platform_dir) } else { platform_dir };
+    let binary_name = export.binary_name();
+    let output_file = if pck_only { format!("{}.pck", binary_name) } else { platform.output_file_name(binary_name) };
+    Path::new(&platform_dir).join(output_file)
+}
+
+/// A `[preset.N]` section followed by the `[preset.N.options]` Godot expects after each preset.
+fn preset_section(index: usize, platform: Platform, builds_dir: &str, config: &Config) -> String {
+    let export = &config.export;
+    let name = preset_name(platform, export);
+    let platform_name = platform.export_name();
+    // Godot strips visuals from a dedicated server export and adds the "dedicated_server" feature tag.
+    let server = export.role == Role::Server;
+    let custom_features = export.preset_features();
+    // Everything but the engine's own files goes into the encrypted part of the pack.
+    let encrypt = export.encrypt;
+    let encryption_filter = if encrypt { "*" } else { "" };
+    let export_path = Path::new(builds_dir).join(export_file(export, platform, false, false)).display().to_string().replace('\\', "/");
+    // Plain text files are only exported when they're explicitly included.
+    let mut include = vec![build_info::BUILD_INFO_FILE];
+    include.extend(export.include_filter.iter().map(|glob| glob.trim()).filter(|glob| *glob != build_info::BUILD_INFO_FILE));
+    let include_filter = include.join(", ");
+    let exclude_filter = export.exclude_filter.iter().map(|glob| glob.trim()).collect::<Vec<_>>().join(", ");
+
+    let mut section = format!(r#"
+[preset.{index}]
+
+name="{name}"
+platform="{platform_name}"
+runnable=true
+dedicated_server={server}
+custom_features="{custom_features}"
+export_filter="all_resources"
+include_filter="{include_filter}"
+exclude_filter="{exclude_filter}"
+export_path="{export_path}"
+patch_list=PackedStringArray()
+encryption_include_filters="{encryption_filter}"
+encryption_exclude_filters=""
+encrypt_pck={encrypt}
+encrypt_directory={encrypt}
+
+[preset.{index}.options]
+"#);
+
+    section.push_str(&match platform {
+        Platform::Windows | Platform::Linux => desktop_preset_options(export.embed_pck.unwrap_or(false), export.arch(platform)),
+        Platform::Macos => macos_preset_options(&config.macos),
+        Platform::Android => android_preset_options(),
+        Platform::Web => web_preset_options(),
+        Platform::Ios => unreachable!("package doesn't export for iOS"),
+    });
+    section
+}
+
+fn desktop_preset_options(embed_pck: bool, arch: Arch) -> String {
+    // By default the pck goes next to the executable, which is how 'package --pck-only' patches ship.
+    let arch = arch.tag();
+    format!(r#"
+binary_format/embed_pck={embed_pck}
+binary_format/architecture="{arch}"
+texture_format/s3tc_bptc=true
+texture_format/etc2_astc=false
+"#)
+}
+
+fn macos_preset_options(macos: &config::MacosConfig) -> String {
+    // Godot refuses to export a macOS app without a valid bundle identifier.
+    let bundle_identifier = macos.bundle_identifier.as_deref().unwrap_or("com.example.game");
+    let mut options = format!(r#"
+binary_format/architecture="universal"
+application/bundle_identifier="{bundle_identifier}"
+texture_format/s3tc_bptc=true
+texture_format/etc2_astc=true
+"#);
+    if let Some(version) = &macos.version {
+        options.push_str(&format!("application/short_version=\"{version}\"\napplication/version=\"{version}\"\n"));
+    }
+    if let Some(icon) = &macos.icon {
+        options.push_str(&format!("application/icon=\"{icon}\"\n"));
+    }
+    options
+}
+
+fn android_preset_options() -> String {
+    // Keystore paths come from the same variables Godot reads at export time.
+    // Users and passwords are left to the environment so they never end up on disk.
+    let debug_keystore = std::env::var("GODOT_ANDROID_KEYSTORE_DEBUG_PATH").unwrap_or_default();
+    let release_keystore = std::env::var("GODOT_ANDROID_KEYSTORE_RELEASE_PATH").unwrap_or_default();
+
+    format!(r#"
+gradle_build/use_gradle_build=false
+architectures/armeabi-v7a=true
+architectures/arm64-v8a=true
+architectures/x86=false
+architectures/x86_64=true
+keystore/debug="{debug_keystore}"
+keystore/release="{release_keystore}"
+package/unique_name="com.example.$genname"
+package/name="Game"
+"#)
+}
+
+fn web_preset_options() -> String {
+    // GDExtensions are only loadable from the threaded, dynamically linked web build.
+    r#"
+variant/extensions_support=true
+variant/thread_support=true
+html/export_icon=true
+"#
+    .to_string()
+}
+
+fn ensure_emscripten() -> Result<()> {
+    let emcc = if cfg!(target_os = "windows") { "emcc.bat" } else { "emcc" };
+    let mut cmd = Command::new(emcc);
+    cmd.arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    trace_command(&cmd);
+    let found = cmd
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+
+    if !found {
+        anyhow::bail!(
+            "emscripten not found. Install the emsdk, activate it (source emsdk_env.sh) \
+             and run 'rustup toolchain install nightly --component rust-src' for web builds."
+        );
+    }
+
+    Ok(())
+}
+
+fn ensure_android_sdk() -> Result<()> {
+    let sdk = std::env::var_os("ANDROID_HOME").or_else(|| std::env::var_os("ANDROID_SDK_ROOT"));
+
+    match sdk {
+        Some(sdk) if Path::new(&sdk).exists() => Ok(()),
+        Some(sdk) => anyhow::bail!("Android SDK not found at {:?} (from ANDROID_HOME/ANDROID_SDK_ROOT).", sdk),
+        None => anyhow::bail!(
+            "Android SDK not found. Install it (e.g. through Android Studio) and set ANDROID_HOME, \
+             then set 'export/android/android_sdk_path' in the Godot editor settings."
+        ),
+    }
+}
+
+/// Export templates of the requested platforms that aren't installed for the configured version.
+fn missing_export_templates(config: &Config, platforms: &[Platform], debug: bool) -> Result<Vec<PathBuf>> {
+    let version_dir = get_godot_templates_dir()?.join(config.godot.templates_version());
+
+    Ok(platforms
+        .iter()
+        .filter(|platform| config.export.custom_template(**platform).is_none())
+        .map(|platform| version_dir.join(platform.template_for(config.export.arch(*platform), debug)))
+        .filter(|template| !template.exists())
+        .collect())
+}
+
+/// Asks a yes/no question on the terminal. Without a terminal (e.g. in CI) the answer is no.
+fn confirm(question: &str) -> Result<bool> {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn ensure_export_templates(root: &Path, config: &Config, platforms: &[Platform], debug: bool) -> Result<()> {
+    let mut missing = missing_export_templates(config, platforms, debug)?;
+    if !missing.is_empty() {
+        for template in &missing {
+            info!("Missing export template: {:?}", template);
+        }
+        if dry_run(format_args!("offer to download the Godot {} export templates", config.godot.version)) {
+            missing.clear();
+        } else if confirm(&format!("Download the Godot {} export templates now?", config.godot.version))? {
+            install_export_templates(root, config, true, false, true)?;
+            missing = missing_export_templates(config, platforms, debug)?;
+        }
+    }
+
+    // Godot's own "export template not found" error doesn't say which file it looked for.
+    if let Some(template) = missing.first() {
+        anyhow::bail!(
+            "Missing export template at {:?}. Run 'cargo xtask setup' to install the Godot {} export templates.",
+            template,
+            config.godot.version
+        );
+    }
+
+    for platform in platforms {
+        if *platform == Platform::Android {
+            ensure_android_sdk()?;
+        }
+        if *platform == Platform::Web {
+            ensure_emscripten()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn package_game(root: &Path, config: &Config, targets: &[ExportTarget], args: &PackageArgs) -> Result<()> {
+    hooks::run_hooks(root, "pre_package", &config.hooks.pre_package)?;
+    let godot_exe = godot::godot_executable(root, config)?;
+    let game_dir = config.game_dir(root);
+
+    // Checked up front, a missing file shouldn't turn up only after a long export.
+    for extra in &config.export.extra_files {
+        if !root.join(&extra.source).is_file() {
+            anyhow::bail!("Extra file {:?} from [[export.extra_files]] doesn't exist", root.join(&extra.source));
+        }
+    }
+
+    // Ensure build output directory exists
+    let builds_dir = root.join(&config.export.builds_dir);
+    if !builds_dir.exists() && !dry_run(format_args!("create {:?}", builds_dir)) {
+        fs::create_dir_all(&builds_dir)?;
+    }
+
+    let godot_abs = std::path::absolute(&godot_exe)?;
+    let build = manifest::Build::new(root, &game_dir, &config.godot.version);
+    let game_abs = game_dir.canonicalize().unwrap_or(game_dir);
+
+    if args.no_import {
+        info!("Step 1/2: Not importing assets (--no-import)");
+    } else {
+        info!("Step 1/2: Importing assets...");
+        import_assets(root, config)?;
+    }
+
+    let mut outcomes = Vec::new();
+    for target in targets {
+        // A failing platform doesn't stop the others; the results are reported together at the end.
+        let started = Instant::now();
+        let result = export_platform(root, config, target, args, &godot_abs, &game_abs, &builds_dir);
+        if let Err(err) = &result {
+            error!("Exporting \"{}\" failed: {:#}", target.preset, err);
+        }
+        outcomes.push(ExportOutcome { platform: target.platform, result, duration: started.elapsed() });
+    }
+    print_package_summary(&outcomes);
+
+    let artifacts = outcomes
+        .iter()
+        .filter_map(|outcome| match &outcome.result {
+            Ok(Some((path, _))) => Some(manifest::Artifact::new(root, outcome.platform, path)),
+            _ => None,
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if !artifacts.is_empty() {
+        manifest::write_manifests(&builds_dir, &build, &artifacts)?;
+    }
+
+    let failed: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .map(|outcome| outcome.platform.dir_name())
+        .collect();
+    if !failed.is_empty() {
+        anyhow::bail!("{} of {} platforms failed: {}", failed.len(), outcomes.len(), failed.join(", "));
+    }
+
+    hooks::run_hooks(root, "post_package", &config.hooks.post_package)?;
+    if args.publish {
+        let exports: Vec<(Platform, PathBuf)> = outcomes
+            .iter()
+            .filter_map(|outcome| match &outcome.result {
+                Ok(Some((path, _))) => Some((outcome.platform, itch::upload_path(path, args.archive))),
+                _ => None,
+            })
+            .collect();
+        let version = args.archive_version.clone().or_else(|| godot::project_setting(&game_abs, "config/version"));
+        itch::publish(config, &exports, version.as_deref())?;
+    }
+    if args.open_after {
+        let host_export = outcomes.iter().find_map(|outcome| match &outcome.result {
+            Ok(Some((path, _))) if outcome.platform == Platform::host() => path.parent(),
+            _ => None,
+        });
+        match host_export {
+            Some(dir) => open_folder(dir),
+            None => info!("--open-after: nothing was exported for this machine's platform"),
+        }
+    }
+    Ok(())
+}
+
+/// Shows `dir` in the file manager. Never fails the command, the export is done by then.
+fn open_folder(dir: &Path) {
+    if report::json() {
+        debug!("--open-after: skipped with --format json");
+        return;
+    }
+    // Nothing to show a window on in CI or over SSH.
+    let headless = std::env::var_os("CI").is_some()
+        || (cfg!(target_os = "linux") && std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none());
+    if headless {
+        info!("--open-after: no display, the export is in {:?}", dir);
+        return;
+    }
+    let opener = if cfg!(target_os = "windows") {
+        "explorer"
     } else if cfg!(target_os = "macos") {
-        ("macOS", ".zip")
+        "open"
+    } else {
+        "xdg-open"
+    };
+    if dry_run(format_args!("open {:?} with {}", dir, opener)) {
+        return;
+    }
+    let mut cmd = Command::new(opener);
+    cmd.arg(dir);
+    trace_command(&cmd);
+    // All three return once the window is up. explorer exits with 1 even when it opened the
+    // folder, so only failing to start counts.
+    if let Err(err) = cmd.status() {
+        warn!("--open-after: failed to start {}: {}", opener, err);
+    }
+}
+
+/// How exporting one platform went: where the export ended up and its size (nothing in a dry
+/// run), or why it failed.
+struct ExportOutcome {
+    platform: Platform,
+    result: Result<Option<(PathBuf, Option<u64>)>>,
+    duration: std::time::Duration,
+}
+
+/// A preset to export and where the export goes.
+struct ExportTarget {
+    platform: Platform,
+    preset: String,
+    output_path: PathBuf,
+}
+
+/// The presets `ensure_export_presets` generates, exported into the builds folder.
+fn generated_preset_targets(root: &Path, config: &Config, platforms: &[Platform], args: &PackageArgs) -> Vec<ExportTarget> {
+    let builds_dir = root.join(&config.export.builds_dir);
+    platforms
+        .iter()
+        .map(|platform| ExportTarget {
+            platform: *platform,
+            preset: preset_name(*platform, &config.export),
+            output_path: builds_dir.join(export_file(&config.export, *platform, args.debug, args.pck_only)),
+        })
+        .collect()
+}
+
+/// For `--preset`: the named presets of a hand-written export_presets.cfg, each exported to its
+/// own `export_path`, which Godot resolves from the project folder.
+fn user_preset_targets(game_dir: &Path, args: &PackageArgs) -> Result<Vec<ExportTarget>> {
+    let presets_path = game_dir.join("export_presets.cfg");
+    let content = fs::read_to_string(&presets_path)
+        .with_context(|| format!("--preset needs an export_presets.cfg, failed to read {:?}", presets_path))?;
+    let presets = existing_presets(&content);
+
+    args.presets
+        .iter()
+        .map(|name| {
+            let Some(preset) = presets.iter().find(|preset| preset.name == *name) else {
+                let names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+                anyhow::bail!("No preset named \"{}\" in {:?} (available: {})", name, presets_path, names.join(", "));
+            };
+            let platform = Platform::from_export_name(&preset.platform)
+                .with_context(|| format!("The \"{}\" preset is for {}, which package can't export", name, preset.platform))?;
+            if preset.export_path.is_empty() {
+                anyhow::bail!("The \"{}\" preset has no export path. Set one in the Godot editor (Project > Export).", name);
+            }
+            let mut output_path = game_dir.join(preset.export_path.trim_start_matches("res://"));
+            if args.pck_only {
+                output_path.set_extension("pck");
+            }
+            Ok(ExportTarget { platform, preset: name.clone(), output_path })
+        })
+        .collect()
+}
+
+/// For `--list-presets`.
+fn list_presets(game_dir: &Path) -> Result<()> {
+    let presets_path = game_dir.join("export_presets.cfg");
+    if !presets_path.exists() {
+        info!("No export_presets.cfg in {:?} yet, package generates one", game_dir);
+        return Ok(());
+    }
+    let presets = existing_presets(&fs::read_to_string(&presets_path)?);
+    for preset in &presets {
+        if report::json() {
+            report::event("preset", json!({ "name": preset.name, "platform": preset.platform, "export_path": preset.export_path }));
+        } else {
+            println!("{}  ({}, {})", preset.name, preset.platform, if preset.export_path.is_empty() { "no export path" } else { &preset.export_path });
+        }
+    }
+    Ok(())
+}
+
+/// Exports one preset with its extra files, signing, `--run-after` and `--archive`.
+fn export_platform(
+    root: &Path,
+    config: &Config,
+    target: &ExportTarget,
+    args: &PackageArgs,
+    godot_abs: &Path,
+    game_abs: &Path,
+    builds_dir: &Path,
+) -> Result<Option<(PathBuf, Option<u64>)>> {
+    let platform = target.platform;
+    let platform_name = &target.preset;
+    let output_path = target.output_path.clone();
+    let export_dir = output_path.parent().unwrap_or(builds_dir).to_path_buf();
+    if args.clean {
+        clean_export_dir(builds_dir, &export_dir)?;
+    }
+
+    info!("Step 2/2: Exporting project for {}...", platform_name);
+    let export_flag = if args.pck_only {
+        "--export-pack"
+    } else if args.debug {
+        "--export-debug"
     } else {
-        ("Linux", "")
+        "--export-release"
+    };
+    let mut export = Command::new(godot_abs);
+    export
+        .arg("--headless")
+        .arg("--audio-driver").arg("Dummy")
+        .arg("--display-driver").arg("headless")
+        .arg(export_flag)
+        .arg(platform_name)
+        .arg(&output_path) // Don't canonicalize, might not exist yet
+        .current_dir(game_abs);
+    // Goes to the export log like the rest of the output.
+    if config.godot.verbose {
+        export.arg("--verbose");
+    }
+    if dry_run(format_args!("run {:?}", export)) {
+        return Ok(None);
+    }
+
+    // Create the specific platform folder (e.g., builds/linux)
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    trace_command(&export);
+    // Set after logging the command, so the key never shows up in the output.
+    if let Some(key) = &args.encryption_key {
+        export.env("GODOT_SCRIPT_ENCRYPTION_KEY", key);
+    }
+    let log_path = export_dir.join(export_log::LOG_FILE);
+    let mut attempt = 1;
+    let log = loop {
+        let (status_export, log) = report::timed(&format!("export {}", platform.dir_name()), || {
+            export_log::run_captured_with_timeout(&mut export, godot_timeout())
+        })?;
+        fs::write(&log_path, &log)?;
+        let Some(status_export) = status_export else {
+            anyhow::bail!(
+                "Exporting {} was still running after {}s and was stopped (--timeout). Last lines of {:?}:\n{}",
+                platform_name,
+                godot_timeout().unwrap_or_default().as_secs(),
+                log_path,
+                export_log::tail(&log, 20)
+            );
+        };
+        if status_export.success() {
+            break log;
+        }
+        if attempt > args.export_retries {
+            anyhow::bail!(
+                "Godot Export step failed for {}{}. Last lines of {:?}:\n{}",
+                platform_name,
+                if attempt > 1 { format!(" after {} attempts", attempt) } else { String::new() },
+                log_path,
+                export_log::tail(&log, 20)
+            );
+        }
+        // Often a virus scanner holding on to the new executable, which lets go after a moment.
+        let delay = Duration::from_secs(2 * attempt as u64);
+        warn!(
+            "Exporting {} failed ({}), retrying in {}s ({} of {} retries)",
+            platform_name,
+            status_export,
+            delay.as_secs(),
+            attempt,
+            args.export_retries
+        );
+        remove_partial_export(&output_path)?;
+        std::thread::sleep(delay);
+        attempt += 1;
+    };
+    let (errors, warnings) = export_log::summarize(&log, &log_path);
+
+    if args.pck_only {
+        // Godot can exit cleanly without writing anything when the preset is unusable.
+        let size = fs::metadata(&output_path)
+            .with_context(|| format!("Export finished but {:?} was not created", output_path))?
+            .len();
+        info!("Pack exported to {:?} ({})", output_path, format_size(size));
+        report::event(
+            "export",
+            json!({ "platform": platform.dir_name(), "export_path": output_path, "size": size, "errors": errors, "warnings": warnings }),
+        );
+        return Ok(Some((output_path, Some(size))));
+    }
+
+    // Depending on how Godot writes it, the Linux binary can come out without the executable bit.
+    #[cfg(unix)]
+    if platform == Platform::Linux && output_path.exists() {
+        let mut perms = fs::metadata(&output_path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&output_path, perms)?;
+    }
+    // Copied before signing, so the signature covers them.
+    let extra_files: Vec<PathBuf> = config
+        .export
+        .extra_files
+        .iter()
+        .filter(|extra| extra.platforms.is_empty() || extra.platforms.contains(&platform))
+        .map(|extra| root.join(&extra.source))
+        .collect();
+    if !extra_files.is_empty() {
+        if platform == Platform::Macos {
+            if Platform::host() != Platform::Macos {
+                anyhow::bail!("Adding extra files to the macOS export requires running on macOS");
+            }
+            macos::add_frameworks(&output_path, &extra_files)?;
+        } else {
+            for file in &extra_files {
+                let name = file.file_name().with_context(|| format!("{:?} is not a file", file))?;
+                // fs::copy keeps the permissions, so libraries stay executable.
+                fs::copy(file, export_dir.join(name))
+                    .with_context(|| format!("Failed to copy {:?} to {:?}", file, export_dir))?;
+                info!("Copied {:?} to {:?}", name, export_dir);
+            }
+        }
+    }
+    write_locale_override(&export_dir, platform, args.locale.as_deref())?;
+    if platform == Platform::Macos {
+        macos::check_bundle_identifier(&output_path, &config.macos)?;
+        let identity = args.sign.as_ref().or(config.macos.sign_identity.as_ref());
+        if args.notarize && identity.is_none() {
+            anyhow::bail!("--notarize needs a signed app. Pass --sign or set `sign_identity` under [macos].");
+        }
+        if let Some(identity) = identity {
+            if Platform::host() != Platform::Macos {
+                anyhow::bail!("Signing the macOS export requires running on macOS");
+            }
+            let notarize = args.notarize.then_some(&config.macos);
+            macos::sign_export(&output_path, identity, notarize)?;
+        }
+    }
+    if platform == Platform::Windows {
+        windows::sign_export(&output_path, &config.windows)?;
+    }
+    // Before archiving, which moves the macOS export.
+    if args.smoke_test {
+        let timeout = Duration::from_secs(args.smoke_test_timeout);
+        smoke_test::smoke_test(&output_path, platform, args.smoke_test_scene.as_deref(), timeout, &log_path)?;
+    }
+    if args.run_after {
+        run_exported(&output_path, platform, args.remote_debug.as_deref())?;
+    }
+    // Read first, archiving the macOS export renames it.
+    let size = fs::metadata(&output_path).map(|metadata| metadata.len()).ok();
+    let mut shipped = output_path.clone();
+    if args.archive {
+        let version = args
+            .archive_version
+            .clone()
+            .or_else(|| godot::project_setting(game_abs, "config/version"))
+            .unwrap_or_else(|| config.godot.version.clone());
+        let project_name = godot::project_setting(game_abs, "config/name").unwrap_or(config.project.name.clone());
+        let name = archive::archive_name(&project_name, &version, platform);
+        shipped = archive::archive_export(&export_dir, &output_path, platform, &name)?;
+    }
+    info!("Export complete! Find it at: {:?}", export_dir);
+    // Where the export is now, which is the archive once it was renamed into one.
+    let export_path = if output_path.exists() { &output_path } else { &shipped };
+    report::event(
+        "export",
+        json!({ "platform": platform.dir_name(), "export_path": export_path, "size": size, "errors": errors, "warnings": warnings }),
+    );
+    let shipped_size = fs::metadata(&shipped).map(|metadata| metadata.len()).ok();
+    Ok(Some((shipped, shipped_size)))
+}
+
+/// One line per platform: whether it shipped, where, how big and how long it took.
+fn print_package_summary(outcomes: &[ExportOutcome]) {
+    report::event(
+        "package_summary",
+        json!({
+            "platforms": outcomes.iter().map(|outcome| match &outcome.result {
+                Ok(export) => json!({
+                    "platform": outcome.platform.dir_name(),
+                    "status": "ok",
+                    "path": export.as_ref().map(|(path, _)| path),
+                    "size": export.as_ref().and_then(|(_, size)| *size),
+                    "duration_ms": outcome.duration.as_millis() as u64,
+                }),
+                Err(err) => json!({
+                    "platform": outcome.platform.dir_name(),
+                    "status": "failed",
+                    "error": format!("{err:#}"),
+                    "duration_ms": outcome.duration.as_millis() as u64,
+                }),
+            }).collect::<Vec<_>>(),
+        }),
+    );
+    if report::json() || outcomes.len() < 2 {
+        return;
+    }
+
+    let width = outcomes.iter().map(|outcome| outcome.platform.dir_name().len()).max().unwrap_or(0);
+    info!("Package summary:");
+    for outcome in outcomes {
+        let platform = outcome.platform.dir_name();
+        let duration = report::format_duration(outcome.duration);
+        match &outcome.result {
+            Ok(Some((path, size))) => {
+                let size = size.map(format_size).unwrap_or_else(|| "-".to_string());
+                info!("  {platform:<width$}  ok      {size:>9}  {duration:>7}  {}", path.display());
+            }
+            Ok(None) => info!("  {platform:<width$}  skipped (dry run)"),
+            Err(err) => error!("  {platform:<width$}  failed            {duration:>7}  {err:#}"),
+        }
+    }
+}
+
+/// Removes what a failed export left behind before it's tried again: the executable or pack,
+/// and the pack next to it.
+fn remove_partial_export(output_path: &Path) -> Result<()> {
+    for path in [output_path.to_path_buf(), output_path.with_extension("pck")] {
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match removed {
+            Ok(()) => debug!("Removed the partial export {:?}", path),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).with_context(|| format!("Failed to remove the partial export {:?}", path)),
+        }
+    }
+    Ok(())
+}
+
+/// For `--clean`: removes one platform's export folder. Anything that isn't a folder inside the
+/// builds folder is left alone, whatever `--out` points at.
+fn clean_export_dir(builds_dir: &Path, export_dir: &Path) -> Result<()> {
+    if export_dir == builds_dir || !export_dir.starts_with(builds_dir) {
+        anyhow::bail!("--clean: refusing to delete {:?}, it isn't a platform folder in {:?}", export_dir, builds_dir);
+    }
+    if !export_dir.exists() || dry_run(format_args!("remove {:?}", export_dir)) {
+        return Ok(());
+    }
+    let mut entries: Vec<String> = fs::read_dir(export_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    entries.sort();
+    fs::remove_dir_all(export_dir).with_context(|| format!("Failed to remove {:?}", export_dir))?;
+    info!("--clean: removed {:?} with {}", export_dir, if entries.is_empty() { "nothing in it".to_string() } else { entries.join(", ") });
+    Ok(())
+}
+
+/// First line of the override.cfg `--locale` writes, so a later export without it knows the
+/// file isn't the user's and removes it.
+const LOCALE_OVERRIDE_HEADER: &str = "; Written by 'cargo xtask package --locale'";
+
+/// Godot reads an override.cfg next to the executable on top of the project settings, and
+/// `locale/test` makes it use that locale whatever the system's is.
+fn write_locale_override(export_dir: &Path, platform: Platform, locale: Option<&str>) -> Result<()> {
+    let path = export_dir.join("override.cfg");
+    let Some(locale) = locale else {
+        let generated = fs::read_to_string(&path).is_ok_and(|content| content.starts_with(LOCALE_OVERRIDE_HEADER));
+        if generated && !dry_run(format_args!("remove {:?}", path)) {
+            fs::remove_file(&path)?;
+            debug!("Removed the locale override of a previous export");
+        }
+        return Ok(());
+    };
+    if !matches!(platform, Platform::Windows | Platform::Linux) {
+        warn!("--locale only applies to Windows and Linux exports, the {} export uses the system locale", platform.export_name());
+        return Ok(());
+    }
+    if dry_run(format_args!("write {:?} with locale {}", path, locale)) {
+        return Ok(());
+    }
+    let content = format!("{LOCALE_OVERRIDE_HEADER}\n[internationalization]\n\nlocale/test=\"{locale}\"\n");
+    fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+    info!("The export starts in the {} locale ({:?})", locale, path);
+    Ok(())
+}
+
+/// Starts an exported game and waits for it, failing if it crashed or exited with an error.
+fn run_exported(export_path: &Path, platform: Platform, remote_debug: Option<&str>) -> Result<()> {
+    if platform != Platform::host() {
+        info!("--run-after: not starting the {} export, it doesn't run on this machine", platform.export_name());
+        return Ok(());
+    }
+    if dry_run(format_args!("start {:?}", export_path)) {
+        return Ok(());
+    }
+
+    let executable = exported_executable(export_path, platform)?;
+    info!("Starting the exported game {:?}...", executable);
+    let mut cmd = Command::new(&executable);
+    // Only debug exports have the debugger, release templates ignore the flag.
+    if let Some(address) = remote_debug {
+        info!("Connecting to the debugger at {}", address);
+        cmd.arg("--remote-debug").arg(address);
+    }
+    if let Some(dir) = executable.parent() {
+        cmd.current_dir(dir);
+    }
+    trace_command(&cmd);
+    let status = wait_for_godot(&mut cmd, "The exported game")?;
+    report::event("run", json!({ "platform": platform.dir_name(), "exit_code": status.code() }));
+
+    match status.code() {
+        Some(0) => {
+            info!("The exported game exited cleanly");
+            Ok(())
+        }
+        Some(code) => anyhow::bail!("The exported game exited with code {}", code),
+        // No exit code means it was killed by a signal, e.g. a segfault.
+        None => anyhow::bail!("The exported game crashed ({})", status),
     }
 }
 
+/// The executable of an export. The macOS export is a zipped app, which is unpacked next to it.
+fn exported_executable(export_path: &Path, platform: Platform) -> Result<PathBuf> {
+    let executable = if platform == Platform::Macos {
+        let run_dir = export_path.with_extension("run");
+        if run_dir.exists() {
+            fs::remove_dir_all(&run_dir)?;
+        }
+        let mut ditto = Command::new("ditto");
+        ditto.arg("-x").arg("-k").arg(export_path).arg(&run_dir);
+        trace_command(&ditto);
+        if !ditto.status().context("Failed to run ditto")?.success() {
+            anyhow::bail!("Failed to unpack {:?}", export_path);
+        }
+        let app = fs::read_dir(&run_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| path.extension().is_some_and(|ext| ext == "app"))
+            .with_context(|| format!("No .app bundle found in {:?}", export_path))?;
+        fs::read_dir(app.join("Contents/MacOS"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .next()
+            .with_context(|| format!("No executable found in {:?}", app))?
+    } else {
+        export_path.to_path_buf()
+    };
+    Ok(executable)
+}
+
 fn get_godot_templates_dir() -> Result<PathBuf> {
     // Standard Godot paths:
     // Linux: ~/.local/share/godot/export_templates/
@@ -429,3 +3957,165 @@ fn get_godot_templates_dir() -> Result<PathBuf> {
 
     Ok(path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `path` with the `..` and `.` resolved without touching the disk.
+    fn normalize(path: &Path) -> PathBuf {
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    normalized.pop();
+                }
+                std::path::Component::CurDir => {}
+                component => normalized.push(component),
+            }
+        }
+        normalized
+    }
+
+    /// Where Godot writes the generated preset's export, resolved from the project folder.
+    fn preset_export_path(root: &Path, config: &Config, platform: Platform) -> PathBuf {
+        let builds_dir = project_relative(root, config, &config.export.builds_dir).display().to_string().replace('\\', "/");
+        let section = preset_section(0, platform, &builds_dir, config);
+        let export_path = section
+            .lines()
+            .find_map(|line| line.strip_prefix("export_path=\""))
+            .and_then(|value| value.strip_suffix('"'))
+            .expect("the preset has an export_path");
+        normalize(&config.game_dir(root).join(export_path))
+    }
+
+    /// An empty folder of its own for every test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xtask-main-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// The `game` cdylib of a scaffolded workspace.
+    fn game_crate() -> ExtensionCrate {
+        ExtensionCrate { package: "game".to_string(), name: "game".to_string(), uses_gdext: true, features: Vec::new(), staticlib: false }
+    }
+
+    #[test]
+    fn preset_export_path_matches_the_packaged_path() {
+        let root = Path::new("/work/my-game");
+        for (project_dir, builds_dir) in [("game", "builds"), ("client/game", "dist/release"), (".", "out")] {
+            let mut config = Config::default();
+            config.project.dir = PathBuf::from(project_dir);
+            config.export.builds_dir = PathBuf::from(builds_dir);
+            for platform in [Platform::Windows, Platform::Linux, Platform::Web] {
+                let expected = normalize(&root.join(&config.export.builds_dir).join(export_file(&config.export, platform, false, false)));
+                assert_eq!(
+                    preset_export_path(root, &config, platform),
+                    expected,
+                    "--project-dir {} --out {} for {}",
+                    project_dir,
+                    builds_dir,
+                    platform.dir_name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn profiles_map_to_their_target_folder() {
+        assert_eq!(cargo_output_dir("dev"), "debug");
+        assert_eq!(cargo_output_dir("test"), "debug");
+        assert_eq!(cargo_output_dir("release"), "release");
+        assert_eq!(cargo_output_dir("bench"), "release");
+        assert_eq!(cargo_output_dir("foo"), "foo");
+    }
+
+    #[test]
+    fn custom_profile_is_installed_as_the_release_library() {
+        let default = BuildArgs::default();
+        assert_eq!(default.cargo_profile(false), "dev");
+        assert_eq!(default.cargo_profile(true), "release");
+        assert!(!default.release_variant(false));
+        assert!(default.release_variant(true));
+
+        let custom = BuildArgs { profile: Some("foo".to_string()), ..BuildArgs::default() };
+        assert_eq!(custom.cargo_profile(false), "foo");
+        assert_eq!(cargo_output_dir(custom.cargo_profile(false)), "foo");
+        assert!(custom.release_variant(false));
+
+        let dev = BuildArgs { profile: Some("dev".to_string()), ..BuildArgs::default() };
+        assert_eq!(cargo_output_dir(dev.cargo_profile(true)), "debug");
+        assert!(!dev.release_variant(true));
+    }
+
+    #[test]
+    fn custom_profile_artifact_is_found_and_installed() {
+        let root = temp_dir("custom-profile");
+        let metadata = Metadata { packages: Vec::new(), target_directory: root.join("target"), locked_versions: Default::default() };
+        // Not stripped, the fake library isn't one strip could read.
+        let args = BuildArgs { profile: Some("foo".to_string()), no_strip: true, ..BuildArgs::default() };
+        let release = args.release_variant(false);
+
+        // Where cargo writes a `[profile.foo]` build.
+        let target_dir = artifact_dir(&metadata, None, args.cargo_profile(release));
+        assert_eq!(target_dir, root.join("target").join("foo"));
+        fs::create_dir_all(&target_dir).unwrap();
+        fs::write(target_dir.join("libgame.so"), "built with the foo profile").unwrap();
+
+        let game_dir = root.join("game");
+        fs::create_dir_all(&game_dir).unwrap();
+        install_crates(&game_dir, &[game_crate()], &target_dir, Platform::Linux, Arch::X86_64, release, &args, &ExtensionConfig::default())
+            .unwrap();
+        let installed = game_dir.join("bin/game/linux/x86_64/release/libgame.so");
+        assert_eq!(fs::read_to_string(&installed).unwrap(), "built with the foo profile");
+        assert!(!game_dir.join("bin/game/linux/x86_64/debug").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn relocated_project_is_set_up_in_its_own_folder() {
+        let root = temp_dir("relocated");
+        let mut config = Config::default();
+        config.project.dir = PathBuf::from("client");
+        let game_dir = config.game_dir(&root);
+        assert_eq!(game_dir, root.join("client"));
+
+        bootstrap_project(&game_dir, &config).unwrap();
+        let target_dir = root.join("target/debug");
+        fs::create_dir_all(&target_dir).unwrap();
+        fs::write(target_dir.join("libgame.so"), "").unwrap();
+        install_crates(&game_dir, &[game_crate()], &target_dir, Platform::Linux, Arch::X86_64, false, &BuildArgs::default(), &config.extension)
+            .unwrap();
+
+        assert!(game_dir.join("project.godot").is_file());
+        assert!(game_dir.join("main.tscn").is_file());
+        assert!(game_dir.join("game.gdextension").is_file());
+        assert!(game_dir.join("bin/game/linux/x86_64/debug/libgame.so").is_file());
+        assert!(!root.join("game").exists(), "nothing may be created in the default project folder");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn preset_option_is_set_in_an_existing_section() {
+        let content = "[preset.0]\n\nname=\"Linux\"\n\n[preset.0.options]\n\nbinary_format/embed_pck=false\n";
+        let updated = set_preset_option(content, 0, "binary_format/embed_pck", "true");
+        assert_eq!(updated, "[preset.0]\n\nname=\"Linux\"\n\n[preset.0.options]\n\nbinary_format/embed_pck=true\n");
+    }
+
+    #[test]
+    fn preset_without_options_gets_a_section() {
+        let content = "[preset.0]\n\nname=\"Linux\"\n\n[preset.1]\n\nname=\"Web\"\n\n[preset.1.options]\n\nvariant/thread_support=true\n";
+        let updated = set_preset_option(content, 0, "binary_format/embed_pck", "true");
+        assert_eq!(
+            updated,
+            "[preset.0]\n\nname=\"Linux\"\n\n[preset.0.options]\nbinary_format/embed_pck=true\n\n[preset.1]\n\nname=\"Web\"\n\n[preset.1.options]\n\nvariant/thread_support=true\n"
+        );
+        // The last preset in the file.
+        let updated = set_preset_option("[preset.0]\nname=\"Linux\"", 0, "binary_format/embed_pck", "true");
+        assert_eq!(updated, "[preset.0]\nname=\"Linux\"\n\n[preset.0.options]\nbinary_format/embed_pck=true\n\n");
+        // An unknown preset is left alone.
+        assert_eq!(set_preset_option(content, 5, "binary_format/embed_pck", "true"), content);
+    }
+}
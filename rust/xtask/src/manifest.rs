@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use log::info;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::build_info;
+use crate::platform::Platform;
+
+/// File written next to each export, and one level up for the whole run.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// What a `package` run was built from, the same for every platform it exported.
+#[derive(Serialize)]
+pub struct Build {
+    pub godot_version: String,
+    /// Short hash of HEAD, with `-dirty` for uncommitted changes.
+    pub commit: String,
+    pub timestamp: String,
+}
+
+impl Build {
+    pub fn new(root: &Path, game_dir: &Path, godot_version: &str) -> Self {
+        Build {
+            godot_version: godot_version.to_string(),
+            commit: build_info::git_revision(root, game_dir),
+            timestamp: build_info::utc_timestamp(),
+        }
+    }
+}
+
+/// The file a platform's export ships as.
+#[derive(Serialize)]
+pub struct Artifact {
+    pub platform: &'static str,
+    /// Relative to the workspace root, with forward slashes.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(skip)]
+    file: PathBuf,
+}
+
+impl Artifact {
+    pub fn new(root: &Path, platform: Platform, file: &Path) -> Result<Self> {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        Ok(Artifact {
+            platform: platform.dir_name(),
+            path: relative.display().to_string().replace('\\', "/"),
+            size: fs::metadata(file).with_context(|| format!("Failed to read {:?}", file))?.len(),
+            sha256: sha256_file(file)?,
+            file: file.to_path_buf(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct PlatformManifest<'a> {
+    #[serde(flatten)]
+    build: &'a Build,
+    #[serde(flatten)]
+    artifact: &'a Artifact,
+}
+
+#[derive(Serialize)]
+struct RunManifest<'a> {
+    #[serde(flatten)]
+    build: &'a Build,
+    platforms: &'a [Artifact],
+}
+
+/// Writes `manifest.json` next to every artifact, and one listing all of them into `builds_dir`,
+/// for release scripts to pick up instead of parsing the log.
+pub fn write_manifests(builds_dir: &Path, build: &Build, artifacts: &[Artifact]) -> Result<()> {
+    for artifact in artifacts {
+        let dir = artifact.file.parent().unwrap_or(builds_dir);
+        write_json(&dir.join(MANIFEST_FILE), &PlatformManifest { build, artifact })?;
+    }
+    write_json(&builds_dir.join(MANIFEST_FILE), &RunManifest { build, platforms: artifacts })
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let content = serde_json::to_string_pretty(value)?;
+    fs::write(path, content + "\n").with_context(|| format!("Failed to write {:?}", path))?;
+    info!("Wrote {:?}", path);
+    Ok(())
+}
+
+/// Exports can be hundreds of megabytes, so the file is hashed as it's read.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect())
+}
@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The subset of `cargo metadata` output xtask cares about.
+#[derive(Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+    /// Where cargo writes build output, honouring CARGO_TARGET_DIR and `build.target-dir`.
+    pub target_directory: PathBuf,
+    /// Versions Cargo.lock resolved, by package name. Empty before the first build.
+    #[serde(skip)]
+    pub locked_versions: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub targets: Vec<Target>,
+    pub dependencies: Vec<Dependency>,
+    /// The `[features]` table of the package.
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct Dependency {
+    /// Name of the depended-on package, even when it's renamed in Cargo.toml.
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub crate_types: Vec<String>,
+}
+
+impl Metadata {
+    /// Loads the metadata of the workspace members. Dependencies aren't resolved, which would
+    /// need the network; their versions come from Cargo.lock instead.
+    pub fn load(root: &Path) -> Result<Self> {
+        Self::run(Self::command(root), root)
+    }
+
+    fn command(root: &Path) -> Command {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("metadata")
+            .arg("--format-version")
+            .arg("1")
+            .arg("--no-deps")
+            .current_dir(root);
+        cmd
+    }
+
+    fn run(mut cmd: Command, root: &Path) -> Result<Self> {
+        crate::trace_command(&cmd);
+        let output = cmd
+            .output()
+            .context("Failed to run cargo metadata")?;
+
+        if !output.status.success() {
+            anyhow::bail!("cargo metadata failed:\n{}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let mut metadata: Self = serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata output")?;
+        metadata.locked_versions = locked_versions(root);
+        Ok(metadata)
+    }
+
+    /// Version of `package` that Cargo.lock resolved, e.g. "0.4.2".
+    pub fn locked_version(&self, package: &str) -> Option<&str> {
+        self.locked_versions.get(package).map(String::as_str)
+    }
+
+    /// Workspace crates built as a `cdylib`, i.e. the GDExtensions.
+    pub fn cdylib_crates(&self) -> Vec<ExtensionCrate> {
+        self.packages
+            .iter()
+            .flat_map(|package| package.targets.iter().map(move |target| (package, target)))
+            .filter(|(_, target)| target.crate_types.iter().any(|crate_type| crate_type == "cdylib"))
+            .map(|(package, target)| ExtensionCrate {
+                package: package.name.clone(),
+                // Cargo names artifacts after the lib target, with dashes turned into underscores.
+                name: target.name.replace('-', "_"),
+                uses_gdext: package.dependencies.iter().any(|dependency| dependency.name == "godot"),
+                features: package.features.keys().cloned().collect(),
+                staticlib: target.crate_types.iter().any(|crate_type| crate_type == "staticlib"),
+            })
+            .collect()
+    }
+
+    /// Whether `name` is a workspace package or lib target at all, cdylib or not.
+    pub fn has_package(&self, name: &str) -> bool {
+        self.packages
+            .iter()
+            .any(|package| package.name == name || package.targets.iter().any(|target| target.name.replace('-', "_") == name))
+    }
+}
+
+/// The first version of every package in Cargo.lock, which is the only one unless the
+/// dependency tree pulls in several.
+pub fn locked_versions(root: &Path) -> BTreeMap<String, String> {
+    let mut versions = BTreeMap::new();
+    let Some(lock) = fs::read_to_string(root.join("Cargo.lock")).ok().and_then(|content| content.parse::<toml::Table>().ok()) else {
+        return versions;
+    };
+    let packages = lock.get("package").and_then(|packages| packages.as_array()).map(Vec::as_slice).unwrap_or_default();
+    for package in packages {
+        if let (Some(name), Some(version)) = (
+            package.get("name").and_then(|name| name.as_str()),
+            package.get("version").and_then(|version| version.as_str()),
+        ) {
+            versions.entry(name.to_string()).or_insert_with(|| version.to_string());
+        }
+    }
+    versions
+}
+
+/// A workspace crate built as a `cdylib`.
+pub struct ExtensionCrate {
+    /// Cargo package name, as passed to `cargo build -p`.
+    pub package: String,
+    /// Library name, which the artifacts, bin folder and .gdextension file are named after.
+    pub name: String,
+    /// Whether the crate depends on gdext (the `godot` crate).
+    pub uses_gdext: bool,
+    /// Features the crate declares.
+    pub features: Vec<String>,
+    /// Whether the crate is also built as a `staticlib`, which iOS needs.
+    pub staticlib: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+
+    #[test]
+    fn target_directory_follows_cargo_target_dir() {
+        let root = std::env::temp_dir().join(format!("xtask-metadata-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"game\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\"]\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        // Somewhere else than <root>/target, like a shared CI cache.
+        let elsewhere = root.join("ci-cache").join("target");
+
+        let mut cmd = Metadata::command(&root);
+        cmd.env("CARGO_TARGET_DIR", &elsewhere);
+        let metadata = Metadata::run(cmd, &root).unwrap();
+        assert!(metadata.target_directory.ends_with("ci-cache/target"), "{:?}", metadata.target_directory);
+
+        // The artifacts are looked up there, not in <root>/target.
+        let file_name = Platform::host().library_file_name(&metadata.cdylib_crates()[0].name);
+        let artifact = crate::artifact_dir(&metadata, None, "release").join(&file_name);
+        assert_eq!(artifact, metadata.target_directory.join("release").join(&file_name));
+        let cross = crate::artifact_dir(&metadata, Some("aarch64-unknown-linux-gnu"), "release");
+        assert!(cross.ends_with("ci-cache/target/aarch64-unknown-linux-gnu/release"), "{:?}", cross);
+        assert!(!cross.starts_with(root.join("target")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
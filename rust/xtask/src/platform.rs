@@ -0,0 +1,214 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// A platform the game can be exported to, or for iOS only built for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Windows,
+    Linux,
+    Macos,
+    Android,
+    Web,
+    /// Built with `--target aarch64-apple-ios` as a static library. The export goes through
+    /// Xcode, so it's no `--platform` or `export.platforms` value.
+    #[value(skip)]
+    #[serde(skip_deserializing)]
+    Ios,
+}
+
+impl Platform {
+    /// The platform xtask is currently running on.
+    pub fn host() -> Self {
+        if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::Macos
+        } else {
+            Platform::Linux
+        }
+    }
+
+    /// Godot's name for the export platform, also used as the generated preset name.
+    pub fn export_name(self) -> &'static str {
+        match self {
+            Platform::Windows => "Windows Desktop",
+            Platform::Linux => "Linux",
+            Platform::Macos => "macOS",
+            Platform::Android => "Android",
+            Platform::Web => "Web",
+            Platform::Ios => "iOS",
+        }
+    }
+
+    /// The platform of a preset's `platform=` name, the reverse of `export_name`. Godot 4.0 and
+    /// 4.1 called Linux "Linux/X11".
+    pub fn from_export_name(name: &str) -> Option<Self> {
+        match name {
+            "Windows Desktop" => Some(Platform::Windows),
+            "Linux" | "Linux/X11" | "Linux/BSD" => Some(Platform::Linux),
+            "macOS" => Some(Platform::Macos),
+            "Android" => Some(Platform::Android),
+            "Web" => Some(Platform::Web),
+            _ => None,
+        }
+    }
+
+    /// Folder name used under `builds/`.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Platform::Windows => "windows",
+            Platform::Linux => "linux",
+            Platform::Macos => "macos",
+            Platform::Android => "android",
+            Platform::Web => "web",
+            Platform::Ios => "ios",
+        }
+    }
+
+    /// Extension of the exported file (the macOS export is a zipped .app).
+    pub fn output_ext(self) -> &'static str {
+        match self {
+            Platform::Windows => ".exe",
+            Platform::Linux => "",
+            Platform::Macos => ".zip",
+            Platform::Android => ".apk",
+            Platform::Web => ".html",
+            Platform::Ios => ".ipa",
+        }
+    }
+
+    /// File name of the exported game inside `builds/<platform>/`, e.g. "my-rust-game.exe".
+    pub fn output_file_name(self, binary_name: &str) -> String {
+        match self {
+            // Browsers look for index.html when the folder is served as-is.
+            Platform::Web => "index.html".to_string(),
+            _ => format!("{}{}", binary_name, self.output_ext()),
+        }
+    }
+
+    /// File name of the compiled extension library for this platform.
+    pub fn library_file_name(self, crate_name: &str) -> String {
+        match self {
+            Platform::Windows => format!("{crate_name}.dll"),
+            Platform::Linux | Platform::Android => format!("lib{crate_name}.so"),
+            Platform::Macos => format!("lib{crate_name}.dylib"),
+            Platform::Web => format!("{crate_name}.wasm"),
+            // Apps can't load libraries at runtime, Xcode links the extension into the game.
+            Platform::Ios => format!("lib{crate_name}.a"),
+        }
+    }
+
+    /// Export template Godot needs in the templates folder for a release export.
+    pub fn release_template(self) -> &'static str {
+        match self {
+            Platform::Windows => "windows_release_x86_64.exe",
+            Platform::Linux => "linux_release.x86_64",
+            Platform::Macos => "macos.zip",
+            Platform::Android => "android_release.apk",
+            // GDExtensions need the dynamically linked (dlink) web template.
+            Platform::Web => "web_dlink_release.zip",
+            Platform::Ios => "ios.zip",
+        }
+    }
+
+    /// Export template for an `arch` export. Only Windows and Linux have one per architecture,
+    /// the others ship a single template for every device.
+    pub fn template_for(self, arch: Arch, debug: bool) -> String {
+        let variant = if debug { "debug" } else { "release" };
+        match (self, arch) {
+            (Platform::Windows, Arch::X86_64 | Arch::Arm64) => format!("windows_{variant}_{}.exe", arch.tag()),
+            (Platform::Linux, _) => format!("linux_{variant}.{}", arch.tag()),
+            _ if debug => self.debug_template().to_string(),
+            _ => self.release_template().to_string(),
+        }
+    }
+
+    /// Export template Godot needs in the templates folder for a debug export.
+    pub fn debug_template(self) -> &'static str {
+        match self {
+            Platform::Windows => "windows_debug_x86_64.exe",
+            Platform::Linux => "linux_debug.x86_64",
+            // The macOS archive bundles both the debug and release templates.
+            Platform::Macos => "macos.zip",
+            Platform::Android => "android_debug.apk",
+            Platform::Web => "web_dlink_debug.zip",
+            Platform::Ios => "ios.zip",
+        }
+    }
+}
+
+/// CPU architecture, named after the matching .gdextension feature tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Arm64,
+    /// 32-bit ARM (armv7), which only Android still ships.
+    Arm32,
+}
+
+impl Arch {
+    /// The architecture xtask was compiled for, which is what a plain `cargo build` targets.
+    pub fn host() -> Self {
+        if cfg!(target_arch = "aarch64") {
+            Arch::Arm64
+        } else {
+            Arch::X86_64
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::Arm64 => "arm64",
+            Arch::Arm32 => "arm32",
+        }
+    }
+}
+
+/// Resolves the platform and architecture a Rust target triple builds for,
+/// e.g. `aarch64-unknown-linux-gnu` -> (Linux, Arm64).
+pub fn parse_triple(triple: &str) -> Option<(Platform, Arch)> {
+    let arch = match triple.split('-').next()? {
+        "x86_64" => Arch::X86_64,
+        "aarch64" | "arm64" => Arch::Arm64,
+        "armv7" | "armv7a" | "thumbv7neon" => Arch::Arm32,
+        _ => return None,
+    };
+
+    let platform = if triple.contains("android") {
+        Platform::Android
+    } else if triple == "aarch64-apple-ios" {
+        // Only devices, the simulator targets would need a .gdextension tag of their own.
+        Platform::Ios
+    } else if triple.contains("windows") {
+        Platform::Windows
+    } else if triple.contains("apple-darwin") {
+        Platform::Macos
+    } else if triple.contains("linux") {
+        Platform::Linux
+    } else {
+        return None;
+    };
+
+    Some((platform, arch))
+}
+
+/// The platform and architecture a plain `cargo build` targets, i.e. the host of the Rust
+/// toolchain. That isn't always what xtask was compiled for, e.g. a prebuilt xtask next to an
+/// x86_64 toolchain running under Rosetta.
+pub fn default_target() -> (Platform, Arch) {
+    rustc_host()
+        .and_then(|triple| parse_triple(&triple))
+        .unwrap_or((Platform::host(), Arch::host()))
+}
+
+fn rustc_host() -> Option<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("-vV").output().ok().filter(|output| output.status.success())?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+}
@@ -0,0 +1,107 @@
+use clap::ValueEnum;
+use log::{debug, info};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How xtask talks to whoever runs it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Progress messages for people
+    #[default]
+    Human,
+    /// One JSON object per line, for CI
+    Json,
+}
+
+/// Set once from `--format`, like the log level.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_format(format: OutputFormat) {
+    JSON.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Writes `{"event": <event>, ...fields}` to stdout with `--format json`. People already see
+/// the same information in the log messages, so it's a no-op otherwise.
+pub fn event(event: &str, fields: Value) {
+    if !json() {
+        return;
+    }
+    let mut object = json!({ "event": event });
+    if let (Some(object), Value::Object(fields)) = (object.as_object_mut(), fields) {
+        object.extend(fields);
+    }
+    println!("{object}");
+}
+
+/// Major steps timed so far, for the summary at the end.
+static STEPS: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// Runs a major step of a command (a download, the build, an export) and prints how long it took.
+pub fn timed<T>(step: &str, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    let duration = started.elapsed();
+    info!("{} took {}", step, format_duration(duration));
+    event("step", json!({ "name": step, "duration_ms": duration.as_millis() as u64 }));
+    STEPS.lock().unwrap().push((step.to_string(), duration));
+    result
+}
+
+/// Like [`timed`] for the parts of a step, which are only shown with `-v` and left out of the summary.
+pub fn timed_detail<T>(step: &str, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    let duration = started.elapsed();
+    debug!("{} took {}", step, format_duration(duration));
+    event("step", json!({ "name": step, "duration_ms": duration.as_millis() as u64, "detail": true }));
+    result
+}
+
+/// Lists the time of every major step and the whole run. JSON output has the same in its
+/// `step` and `finished` events.
+pub fn print_summary(total: Duration) {
+    let steps = STEPS.lock().unwrap();
+    if json() || steps.is_empty() {
+        return;
+    }
+    let width = steps.iter().map(|(step, _)| step.len()).max().unwrap_or(0).max("total".len());
+    info!("Timings:");
+    for (step, duration) in steps.iter() {
+        info!("  {:<width$}  {}", step, format_duration(*duration));
+    }
+    info!("  {:<width$}  {}", "total", format_duration(total));
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() >= 60 {
+        format!("{}m {:02}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    } else if duration.as_secs() >= 1 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// The last event of every run: whether the command succeeded and, if not, the error with its
+/// whole context chain, outermost first.
+pub fn finished(result: &anyhow::Result<()>, duration: Duration) {
+    let duration_ms = duration.as_millis() as u64;
+    match result {
+        Ok(()) => event("finished", json!({ "status": "ok", "duration_ms": duration_ms })),
+        Err(err) => event(
+            "finished",
+            json!({
+                "status": "error",
+                "duration_ms": duration_ms,
+                "error": err.to_string(),
+                "causes": err.chain().skip(1).map(|cause| cause.to_string()).collect::<Vec<_>>(),
+            }),
+        ),
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+
+/// Creates a workspace laid out like this template in `dir`: an extension crate under
+/// `rust/src/<name>`, a copy of this xtask, and an empty `game/` project folder.
+pub fn new_project(dir: &Path, name: &str, force: bool) -> Result<()> {
+    let crate_name = crate_name(name)?;
+
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() && !force {
+        anyhow::bail!("{:?} is not empty. Pass --force to scaffold into it anyway.", dir);
+    }
+
+    info!("Creating project '{}' in {:?}...", crate_name, dir);
+
+    write_file(dir, "Cargo.toml", &format!(r#"[workspace]
+members = [
+    "rust/src/{crate_name}",
+    "rust/xtask",
+]
+resolver = "2"
+"#))?;
+
+    write_file(dir, ".cargo/config.toml", r#"[alias]
+xtask = "run -p xtask --"
+"#)?;
+
+    let crate_dir = format!("rust/src/{crate_name}");
+    write_file(dir, &format!("{crate_dir}/Cargo.toml"), &format!(r#"[package]
+name = "{crate_name}"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+godot = {{ git = "https://github.com/godot-rust/gdext", branch = "master" }}
+"#))?;
+
+    write_file(dir, &format!("{crate_dir}/src/lib.rs"), r#"use godot::prelude::*;
+
+struct Extension;
+
+#[gdextension]
+unsafe impl ExtensionLibrary for Extension {}
+"#)?;
+
+    // Ship the build tool along so 'cargo xtask' works in the new project right away.
+    let xtask_src = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let xtask_dst = dir.join("rust/xtask");
+    fs::create_dir_all(xtask_dst.join("src"))?;
+    fs::copy(xtask_src.join("Cargo.toml"), xtask_dst.join("Cargo.toml"))
+        .context("Failed to copy the xtask manifest")?;
+    let options = fs_extra::dir::CopyOptions::new().content_only(true).overwrite(true);
+    fs_extra::dir::copy(xtask_src.join("src"), xtask_dst.join("src"), &options)
+        .context("Failed to copy the xtask sources")?;
+
+    // project.godot and friends are bootstrapped on the first run.
+    fs::create_dir_all(dir.join("game"))?;
+    update_gitignore(dir, &Config::default())?;
+
+    info!("Done! Run 'cargo xtask setup' then 'cargo xtask editor' inside {:?}.", dir);
+    Ok(())
+}
+
+/// Adds what xtask generates to the workspace `.gitignore`, creating it if needed. Lines that
+/// are already there (with or without the leading or trailing slash) aren't added again, so
+/// it's safe to run any number of times.
+pub fn update_gitignore(dir: &Path, config: &Config) -> Result<()> {
+    let path = dir.join(".gitignore");
+    let existing = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+    let normalize = |line: &str| line.trim().trim_start_matches('/').trim_end_matches('/').to_string();
+    let present: Vec<String> = existing.lines().map(normalize).collect();
+
+    let missing: Vec<String> = gitignore_entries(config)
+        .into_iter()
+        .filter(|entry| !present.contains(&normalize(entry)))
+        .collect();
+    if missing.is_empty() {
+        info!(".gitignore already ignores the generated files");
+        return Ok(());
+    }
+    if crate::dry_run(format_args!("add {} to {:?}", missing.join(", "), path)) {
+        return Ok(());
+    }
+
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    content.push_str("# Generated by cargo xtask\n");
+    for entry in &missing {
+        content.push_str(entry);
+        content.push('\n');
+    }
+    fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+    info!("Added {} to {:?}", missing.join(", "), path);
+    Ok(())
+}
+
+/// The build output, the Godot installs and the editor's cache, anchored at the workspace root.
+fn gitignore_entries(config: &Config) -> Vec<String> {
+    let mut entries = vec!["/target/".to_string()];
+    // Folders configured outside the workspace aren't anybody's business here.
+    for folder in [&config.godot.install_dir, &config.export.builds_dir] {
+        if folder.is_relative() {
+            entries.push(format!("/{}/", folder.display().to_string().replace('\\', "/").trim_matches('/')));
+        }
+    }
+    if config.project.dir.is_relative() {
+        let game = config.project.dir.display().to_string().replace('\\', "/").trim_matches('/').to_string();
+        entries.push(format!("/{}/{}/", game, config.extension.bin_dir()));
+        entries.push(format!("/{}/.godot/", game));
+        entries.push(format!("/{}/{}", game, crate::build_info::BUILD_INFO_FILE));
+    }
+    entries
+}
+
+/// Turns a project name into a valid crate name, e.g. "My Game" -> "my_game".
+fn crate_name(name: &str) -> Result<String> {
+    let crate_name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+
+    if crate_name.is_empty() || crate_name.starts_with(|c: char| c.is_ascii_digit()) {
+        anyhow::bail!("'{}' can't be used as a crate name", name);
+    }
+
+    Ok(crate_name)
+}
+
+fn write_file(dir: &Path, relative: &str, content: &str) -> Result<()> {
+    let path = dir.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))
+}
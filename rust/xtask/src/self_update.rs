@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use log::info;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use crate::platform::{Arch, Platform};
+
+/// Where `self-update` looks for new releases unless `--feed` says otherwise.
+pub const DEFAULT_FEED: &str = "https://github.com/VALERE91/RustyGodot/releases/latest/download/xtask-release.json";
+
+/// The release feed: the latest version and a binary per platform.
+///
+/// ```json
+/// { "version": "0.2.0",
+///   "assets": { "linux-x86_64": { "url": "https://.../xtask-linux-x86_64.zip", "sha256": "..." } } }
+/// ```
+#[derive(Deserialize)]
+struct Feed {
+    version: String,
+    assets: BTreeMap<String, Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    /// The bare executable, or a zip containing it.
+    url: String,
+    sha256: String,
+}
+
+/// Replaces the running xtask with the latest release from `feed`, if it is newer.
+/// With `check`, only reports whether there is one.
+pub fn self_update(feed: &str, check: bool) -> Result<()> {
+    // `cargo xtask` runs a binary cargo rebuilds from source, which a download would only shadow.
+    if std::env::var_os("CARGO_MANIFEST_DIR").is_some() {
+        anyhow::bail!("xtask is running through cargo, update the sources instead (e.g. git pull)");
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    let feed: Feed = serde_json::from_slice(&crate::download::fetch(feed)?)
+        .with_context(|| format!("Failed to parse the release feed at {}", feed))?;
+    info!("Current version: {}, latest version: {}", current, feed.version);
+    if parse_version(&feed.version) <= parse_version(current) {
+        info!("xtask is up to date");
+        return Ok(());
+    }
+    if check {
+        info!("Run 'xtask self-update' to update");
+        return Ok(());
+    }
+
+    let key = format!("{}-{}", Platform::host().dir_name(), Arch::host().tag());
+    let asset = feed
+        .assets
+        .get(&key)
+        .with_context(|| format!("xtask {} has no binary for {}", feed.version, key))?;
+
+    let exe = std::env::current_exe()?;
+    if crate::dry_run(format_args!("download {} and replace {:?}", asset.url, exe)) {
+        return Ok(());
+    }
+
+    let bytes = crate::download::fetch(&asset.url)?;
+    let checksum: String = Sha256::digest(&bytes).iter().map(|byte| format!("{byte:02x}")).collect();
+    if checksum != asset.sha256.to_ascii_lowercase() {
+        anyhow::bail!(
+            "Checksum mismatch for {}: expected SHA-256 {}, got {}. Nothing was replaced.",
+            asset.url,
+            asset.sha256,
+            checksum
+        );
+    }
+    let binary = if asset.url.ends_with(".zip") { unzip_executable(&bytes)? } else { bytes };
+
+    replace_executable(&exe, &binary)?;
+    info!("Updated xtask {} -> {}", current, feed.version);
+    crate::report::event("self_update", serde_json::json!({ "from": current, "to": feed.version }));
+    Ok(())
+}
+
+/// The xtask executable inside a release zip, wherever it is in there.
+fn unzip_executable(bytes: &[u8]) -> Result<Vec<u8>> {
+    let name = if cfg!(windows) { "xtask.exe" } else { "xtask" };
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_file() && file.name().rsplit('/').next() == Some(name) {
+            let mut binary = Vec::new();
+            file.read_to_end(&mut binary)?;
+            return Ok(binary);
+        }
+    }
+    anyhow::bail!("The release archive has no {}", name)
+}
+
+/// Writes the new binary next to the old one and renames it over it, so an interrupted
+/// update never leaves a half-written executable behind.
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let partial = exe.with_extension("part");
+    let _partial_guard = crate::interrupt::temp_path(&partial);
+    fs::write(&partial, binary).with_context(|| format!("Failed to write {:?}", partial))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&partial, fs::Permissions::from_mode(0o755))?;
+    }
+    // Windows can't replace a running executable, but it can rename it out of the way.
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).with_context(|| format!("Failed to move {:?} aside", exe))?;
+    }
+    fs::rename(&partial, exe).with_context(|| format!("Failed to replace {:?}", exe))?;
+    Ok(())
+}
+
+/// "1.2.3" (with an optional leading "v") as numbers, so "0.10.0" sorts after "0.9.0".
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['.', '-'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
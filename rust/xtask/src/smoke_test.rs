@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde_json::json;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::export_log;
+use crate::platform::Platform;
+
+/// Main loop iterations the game runs for before quitting on its own, enough to load the
+/// extension and the main scene.
+const QUIT_AFTER: &str = "2";
+
+/// Boots the export headless and fails if it crashes, hangs or logs an error, which is how a
+/// library that doesn't load shows up. Its output is appended to the export log.
+pub fn smoke_test(export_path: &Path, platform: Platform, scene: Option<&str>, timeout: Duration, log_path: &Path) -> Result<()> {
+    if platform != Platform::host() {
+        info!("--smoke-test: skipping the {} export, it doesn't run on this machine", platform.export_name());
+        return Ok(());
+    }
+    if crate::dry_run(format_args!("boot {:?} headless", export_path)) {
+        return Ok(());
+    }
+
+    let executable = crate::exported_executable(export_path, platform)?;
+    info!("Smoke testing {:?}...", executable);
+    let mut cmd = Command::new(&executable);
+    cmd.arg("--headless").arg("--quit-after").arg(QUIT_AFTER);
+    if let Some(scene) = scene {
+        cmd.arg(scene);
+    }
+    if let Some(dir) = executable.parent() {
+        cmd.current_dir(dir);
+    }
+    crate::trace_command(&cmd);
+    let (status, log) = crate::report::timed(&format!("smoke test {}", platform.dir_name()), || {
+        export_log::run_captured_with_timeout(&mut cmd, Some(timeout))
+    })?;
+    append_to_log(log_path, &log)?;
+
+    let (errors, _) = export_log::errors_and_warnings(&log);
+    crate::report::event(
+        "smoke_test",
+        json!({ "platform": platform.dir_name(), "exit_code": status.and_then(|status| status.code()), "errors": errors.len() }),
+    );
+    let Some(status) = status else {
+        anyhow::bail!(
+            "The exported game was still running after {}s. Last lines of {:?}:\n{}",
+            timeout.as_secs(),
+            log_path,
+            export_log::tail(&log, 20)
+        );
+    };
+    if !status.success() {
+        // No exit code means it was killed by a signal, e.g. a segfault.
+        let exit = status.code().map_or_else(|| format!("crashed ({})", status), |code| format!("exited with code {}", code));
+        anyhow::bail!("The exported game {}. Last lines of {:?}:\n{}", exit, log_path, export_log::tail(&log, 20));
+    }
+    if !errors.is_empty() {
+        for message in &errors {
+            warn!("  ERROR: {}", message);
+        }
+        anyhow::bail!("The exported game reported {} error(s) while booting, see {:?}", errors.len(), log_path);
+    }
+    info!("The exported game booted cleanly");
+    Ok(())
+}
+
+fn append_to_log(log_path: &Path, log: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .with_context(|| format!("Failed to open {:?}", log_path))?;
+    writeln!(file, "\n--- smoke test ---")?;
+    file.write_all(log.as_bytes())?;
+    debug!("Appended the smoke test output to {:?}", log_path);
+    Ok(())
+}
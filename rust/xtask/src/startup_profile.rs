@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use log::info;
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::export_log;
+
+/// Written into the cargo target folder, with the time every line came in.
+pub const LOG_FILE: &str = "startup-profile.log";
+
+/// What godot-rust prints once the extension's entry point ran.
+const GDEXT_INIT: &str = "Initialize godot-rust";
+
+/// Starts the game with `--verbose --quit-after 1` and reports how long it took to get to the
+/// extension being initialized, the main scene starting to load and the first frame being done.
+/// Godot doesn't time its log, so the times are when each line came in, measured from the start.
+pub fn profile_startup(
+    root: &Path,
+    config: &Config,
+    scene: Option<&str>,
+    launch: &crate::LaunchArgs,
+    game_args: &[String],
+    log_path: &Path,
+) -> Result<()> {
+    let mut cmd = crate::godot_command(root, config, false)?;
+    launch.apply(&mut cmd);
+    // One frame is enough to have the main scene in the tree.
+    cmd.arg("--verbose").arg("--quit-after").arg("1");
+    if let Some(scene) = scene {
+        cmd.arg(scene);
+    }
+    if !game_args.is_empty() {
+        cmd.arg("--").args(game_args);
+    }
+    if crate::dry_run(format_args!("run {:?} and time its startup", cmd)) {
+        return Ok(());
+    }
+
+    info!("Profiling the startup...");
+    crate::trace_command(&cmd);
+    let started = Instant::now();
+    let (status, lines) = export_log::run_timed(&mut cmd, crate::godot_timeout())?;
+    // Quitting after the first frame takes next to nothing, so that's when the frame was done.
+    let total = started.elapsed();
+    write_log(log_path, &lines)?;
+    let Some(status) = status else {
+        anyhow::bail!("Godot was still running after the --timeout, see {:?}", log_path);
+    };
+    if !status.success() {
+        anyhow::bail!("Godot exited with {} while starting up, see {:?}", status, log_path);
+    }
+
+    let main_scene = scene.map(str::to_string).or_else(|| crate::godot::project_setting(&config.game_dir(root), "run/main_scene"));
+    let find = |matches: &dyn Fn(&str) -> bool| lines.iter().find(|(_, line)| matches(line)).map(|(elapsed, _)| *elapsed);
+    let gdext_init = find(&|line| line.contains(GDEXT_INIT));
+    let scene_start = main_scene
+        .as_deref()
+        .and_then(|scene| find(&|line| line.contains("Loading resource: ") && line.contains(scene)));
+
+    let ms = |elapsed: Option<Duration>| elapsed.map(|elapsed| elapsed.as_millis() as u64);
+    if crate::report::json() {
+        crate::report::event(
+            "startup",
+            json!({ "gdext_init_ms": ms(gdext_init), "scene_load_ms": ms(scene_start), "total_ms": ms(Some(total)), "log": log_path }),
+        );
+        return Ok(());
+    }
+    let show = |elapsed: Option<Duration>| elapsed.map_or_else(|| "not found".to_string(), |elapsed| format!("{} ms", elapsed.as_millis()));
+    info!("Startup profile (the full log is in {:?}):", log_path);
+    info!("  extension initialized  {}", show(gdext_init));
+    info!("  main scene loading     {} ({})", show(scene_start), main_scene.as_deref().unwrap_or("no main scene"));
+    info!("  first frame done       {}", show(Some(total)));
+    Ok(())
+}
+
+fn write_log(log_path: &Path, lines: &[(Duration, String)]) -> Result<()> {
+    let mut log = String::new();
+    for (elapsed, line) in lines {
+        let _ = writeln!(log, "[{:>6} ms] {}", elapsed.as_millis(), export_log::strip_ansi(line));
+    }
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(log_path, log).with_context(|| format!("Failed to write {:?}", log_path))
+}
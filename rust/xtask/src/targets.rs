@@ -0,0 +1,181 @@
+use anyhow::Result;
+use serde_json::json;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::platform::Platform;
+
+const PLATFORMS: [Platform; 6] = [Platform::Windows, Platform::Linux, Platform::Macos, Platform::Android, Platform::Web, Platform::Ios];
+
+/// The state of one thing a platform needs.
+enum Check {
+    Ready,
+    /// Nothing to check for this platform.
+    NotNeeded,
+    /// Couldn't be checked, e.g. the Rust targets without rustup.
+    Unknown(String),
+    Missing(String),
+}
+
+impl Check {
+    fn label(&self) -> &'static str {
+        match self {
+            Check::Ready => "ok",
+            Check::NotNeeded => "-",
+            Check::Unknown(_) => "unknown",
+            Check::Missing(_) => "missing",
+        }
+    }
+
+    fn hint(&self) -> Option<&str> {
+        match self {
+            Check::Unknown(hint) | Check::Missing(hint) => Some(hint),
+            Check::Ready | Check::NotNeeded => None,
+        }
+    }
+}
+
+/// For the `list-targets` command: whether every platform has its Rust targets, its export
+/// templates and its external tools on this machine, with what to do about the missing ones.
+/// Nothing in it fails, a platform that isn't ready is only reported.
+pub fn list_targets(config: &Config) -> Result<()> {
+    let installed = crate::installed_rust_targets();
+    let version_dir = crate::get_godot_templates_dir()?.join(config.godot.templates_version());
+
+    let mut rows = Vec::new();
+    for platform in PLATFORMS {
+        let rust = rust_targets(platform, installed.as_deref());
+        let missing_templates: Vec<&str> = [platform.release_template(), platform.debug_template()]
+            .into_iter()
+            .fold(Vec::new(), |mut files, file| {
+                // macOS uses the same archive for both.
+                if !files.contains(&file) && !version_dir.join(file).exists() {
+                    files.push(file);
+                }
+                files
+            });
+        let templates = if missing_templates.is_empty() {
+            Check::Ready
+        } else {
+            Check::Missing(format!(
+                "{} not in {:?}, run 'cargo xtask setup' to install the Godot {} export templates",
+                missing_templates.join(" and "),
+                version_dir,
+                config.godot.version
+            ))
+        };
+        let tools = tools(platform);
+        rows.push((platform, rust, templates, tools));
+    }
+
+    if crate::report::json() {
+        for (platform, rust, templates, tools) in &rows {
+            let checks = [rust, templates, tools];
+            crate::report::event(
+                "target",
+                json!({
+                    "platform": platform.dir_name(),
+                    "ready": checks.iter().all(|check| !matches!(check, Check::Missing(_))),
+                    "rust": rust.label(),
+                    "templates": templates.label(),
+                    "tools": tools.label(),
+                    "hints": checks.iter().filter_map(|check| check.hint()).collect::<Vec<_>>(),
+                }),
+            );
+        }
+        return Ok(());
+    }
+
+    println!("{:10} {:14} {:18} {:8}", "Platform", "Rust targets", "Export templates", "Tools");
+    for (platform, rust, templates, tools) in &rows {
+        println!("{:10} {:14} {:18} {:8}", platform.dir_name(), rust.label(), templates.label(), tools.label());
+    }
+    let hints: Vec<(Platform, &str)> = rows
+        .iter()
+        .flat_map(|(platform, rust, templates, tools)| [rust, templates, tools].map(|check| (*platform, check.hint())))
+        .filter_map(|(platform, hint)| Some((platform, hint?)))
+        .collect();
+    if !hints.is_empty() {
+        println!();
+        for (platform, hint) in hints {
+            println!("{}: {}", platform.dir_name(), hint);
+        }
+    }
+    Ok(())
+}
+
+/// The Rust targets besides the host's that `build` and `package` compile `platform` with.
+fn required_rust_targets(platform: Platform) -> Result<Vec<&'static str>, String> {
+    let host = Platform::host();
+    match platform {
+        _ if platform == host => Ok(Vec::new()),
+        Platform::Windows => Ok(vec!["x86_64-pc-windows-msvc"]),
+        Platform::Linux => Ok(vec!["x86_64-unknown-linux-gnu"]),
+        Platform::Macos => Err("needs a macOS host, Apple's linker and SDK only run there".to_string()),
+        Platform::Android => Ok(crate::ANDROID_TARGETS.iter().map(|(triple, _)| *triple).collect()),
+        // Built with the nightly toolchain's own std, see `tools`.
+        Platform::Web => Ok(Vec::new()),
+        Platform::Ios if host != Platform::Macos => Err("needs a macOS host with Xcode installed".to_string()),
+        Platform::Ios => Ok(vec!["aarch64-apple-ios"]),
+    }
+}
+
+fn rust_targets(platform: Platform, installed: Option<&[String]>) -> Check {
+    let required = match required_rust_targets(platform) {
+        Ok(required) => required,
+        Err(hint) => return Check::Missing(hint),
+    };
+    if required.is_empty() {
+        return Check::Ready;
+    }
+    let Some(installed) = installed else {
+        return Check::Unknown(format!("rustup isn't available, make sure the toolchain has {}", required.join(", ")));
+    };
+    let missing: Vec<&str> = required.into_iter().filter(|triple| !installed.iter().any(|target| target == triple)).collect();
+    if missing.is_empty() {
+        Check::Ready
+    } else {
+        Check::Missing(format!("run 'rustup target add {}'", missing.join(" ")))
+    }
+}
+
+/// The tools outside of Rust and Godot a platform is built or exported with.
+fn tools(platform: Platform) -> Check {
+    let result = match platform {
+        Platform::Android => crate::android_ndk_toolchain().and_then(|_| crate::ensure_android_sdk()),
+        Platform::Web => nightly_rust_src().and_then(|_| crate::ensure_emscripten()),
+        Platform::Ios if Platform::host() == Platform::Macos => xcode(),
+        Platform::Windows | Platform::Linux | Platform::Macos | Platform::Ios => return Check::NotNeeded,
+    };
+    match result {
+        Ok(()) => Check::Ready,
+        Err(err) => Check::Missing(format!("{:#}", err)),
+    }
+}
+
+/// The web build rebuilds std with `+nightly -Zbuild-std`, which needs nightly's rust-src.
+fn nightly_rust_src() -> Result<()> {
+    let mut cmd = Command::new("rustup");
+    cmd.arg("component").arg("list").arg("--installed").arg("--toolchain").arg("nightly");
+    crate::trace_command(&cmd);
+    let installed = cmd
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .is_some_and(|output| String::from_utf8_lossy(&output.stdout).lines().any(|line| line.trim().starts_with("rust-src")));
+    if !installed {
+        anyhow::bail!("the nightly toolchain with rust-src is missing, run 'rustup toolchain install nightly --component rust-src'");
+    }
+    Ok(())
+}
+
+/// The generated Xcode project is built with Xcode's command line tools.
+fn xcode() -> Result<()> {
+    let mut cmd = Command::new("xcodebuild");
+    cmd.arg("-version").stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null());
+    crate::trace_command(&cmd);
+    if !cmd.status().is_ok_and(|status| status.success()) {
+        anyhow::bail!("Xcode not found, install it from the App Store and run 'xcode-select --install'");
+    }
+    Ok(())
+}
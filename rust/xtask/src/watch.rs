@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use notify::{Event, RecursiveMode, Watcher};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::Config;
+use crate::metadata::Metadata;
+use crate::{build_and_install, godot_command, BuildArgs};
+
+/// How long the sources must stay quiet before a rebuild starts.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Files Godot writes itself while importing, which would otherwise start the next import.
+const IMPORT_OUTPUTS: &[&str] = &["import", "uid", "tmp"];
+
+/// Builds once, opens the editor, then rebuilds on every change under `rust/` until the
+/// editor is closed or the user hits Ctrl-C. The editor stays open across rebuilds and
+/// picks up the re-copied library through Godot's hot-reload.
+///
+/// With `assets`, changes in the project folder are imported headlessly too, outside of
+/// `.godot/` and the installed libraries, so the editor finds them imported when it gets focus.
+pub fn watch(root: &Path, config: &Config, args: &BuildArgs, assets: bool) -> Result<()> {
+    build_and_install(root, config, &Metadata::load(root)?, false, args)?;
+    if args.no_reloadable {
+        warn!("[watch] --no-reloadable is set, the editor has to be restarted to load a rebuild");
+    }
+
+    let mut cmd = godot_command(root, config, true)?;
+    if crate::dry_run(format_args!("run {:?} and rebuild on every change", cmd)) {
+        return Ok(());
+    }
+
+    info!("Launching Godot...");
+    crate::trace_command(&cmd);
+    let mut editor = cmd
+        .spawn()
+        .context("Failed to launch Godot process")?;
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let sources = root.join("rust");
+    watcher.watch(&sources, RecursiveMode::Recursive)?;
+
+    info!("[watch] Watching {:?} for changes (Ctrl-C to stop)", sources);
+    let game_dir = config.game_dir(root);
+    let ignored = vec![game_dir.join(".godot"), game_dir.join(args.extension_settings(config)?.bin_dir())];
+    if assets {
+        watcher.watch(&game_dir, RecursiveMode::Recursive)?;
+        info!("[watch] Watching {:?} for asset changes", game_dir);
+    }
+    let assets = assets.then_some((game_dir.as_path(), ignored.as_slice()));
+
+    loop {
+        if let Some(status) = editor.try_wait()? {
+            info!("[watch] Godot exited ({}), stopping", status);
+            return Ok(());
+        }
+
+        let mut changes = Changes::default();
+        match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(Ok(event)) => changes.add(&event, assets),
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Ok(Err(err)) => {
+                error!("[watch] Watcher error: {}", err);
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => anyhow::bail!("File watcher stopped unexpectedly"),
+        }
+        if !changes.sources && changes.assets.is_empty() {
+            continue;
+        }
+
+        // Editors save in bursts (temp file, rename, metadata), so wait for things to settle.
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            if let Ok(event) = event {
+                changes.add(&event, assets);
+            }
+        }
+
+        if !changes.assets.is_empty() {
+            reimport(root, config, &changes.assets);
+        }
+        if !changes.sources {
+            continue;
+        }
+        let started = Instant::now();
+        // Loaded again for every rebuild, Cargo.toml may have changed in the meantime.
+        match Metadata::load(root).and_then(|metadata| build_and_install(root, config, &metadata, false, args)) {
+            Ok(()) => {
+                info!("[watch] Rebuilt in {:.1}s", started.elapsed().as_secs_f32());
+                if let Err(err) = touch_extensions(&config.game_dir(root)) {
+                    warn!("[watch] Failed to touch the .gdextension files: {:#}", err);
+                }
+            }
+            Err(err) => error!("[watch] Build failed: {:#}", err),
+        }
+    }
+}
+
+/// Bumps the modification time of every .gdextension file in the project. Godot reloads a
+/// `reloadable` extension when it notices the change, the next time the editor has focus.
+fn touch_extensions(game_dir: &Path) -> Result<()> {
+    let now = SystemTime::now();
+    for entry in fs::read_dir(game_dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "gdextension") {
+            fs::File::options().write(true).open(&path)?.set_modified(now)?;
+            debug!("[watch] Touched {:?}", path);
+        }
+    }
+    Ok(())
+}
+
+/// What changed during one burst of events.
+#[derive(Default)]
+struct Changes {
+    sources: bool,
+    assets: Vec<PathBuf>,
+}
+
+impl Changes {
+    /// `assets` is the project folder and what to ignore in it, when assets are watched.
+    fn add(&mut self, event: &Event, assets: Option<(&Path, &[PathBuf])>) {
+        if !(event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove()) {
+            return;
+        }
+        for path in &event.paths {
+            match assets {
+                Some((game_dir, ignored)) if path.starts_with(game_dir) => {
+                    if is_asset(path, ignored) && !self.assets.contains(path) {
+                        self.assets.push(path.clone());
+                    }
+                }
+                _ => self.sources |= is_source(path),
+            }
+        }
+    }
+}
+
+fn is_source(path: &Path) -> bool {
+    // Ignore editor swap/backup files and anything that isn't part of the sources.
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("rs") | Some("toml"))
+}
+
+fn is_asset(path: &Path, ignored: &[PathBuf]) -> bool {
+    if ignored.iter().any(|dir| path.starts_with(dir)) {
+        return false;
+    }
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    // Swap and backup files of image and text editors, and the .gdextension files touched above.
+    !(name.starts_with('.') || name.ends_with('~') || IMPORT_OUTPUTS.contains(&extension) || extension == "gdextension")
+}
+
+/// Runs Godot's headless import for the changed assets. A failure is only logged, the next save
+/// tries again.
+fn reimport(root: &Path, config: &Config, changed: &[PathBuf]) {
+    let game_dir = config.game_dir(root);
+    for path in changed {
+        debug!("[watch] Asset changed: {:?}", path.strip_prefix(&game_dir).unwrap_or(path));
+    }
+    let started = Instant::now();
+    match crate::import_assets(root, config) {
+        Ok(()) => info!("[watch] Reimported {} changed asset(s) in {:.1}s", changed.len(), started.elapsed().as_secs_f32()),
+        Err(err) => error!("[watch] Import failed: {:#}", err),
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use log::{info, trace};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::WindowsConfig;
+use crate::platform::Platform;
+
+/// How the executable is signed: a certificate file with its password, or a certificate from
+/// the Windows certificate store.
+enum Certificate {
+    File { path: PathBuf, password: Option<String> },
+    Thumbprint(String),
+}
+
+/// Authenticode-signs an exported .exe with signtool and verifies the signature, if a
+/// certificate is configured.
+pub fn sign_export(exe: &Path, config: &WindowsConfig) -> Result<()> {
+    let Some(certificate) = certificate(config) else {
+        return Ok(());
+    };
+    if crate::dry_run(format_args!("sign {:?} with signtool", exe)) {
+        return Ok(());
+    }
+    if Platform::host() != Platform::Windows {
+        anyhow::bail!("Signing the Windows export requires running on Windows (signtool)");
+    }
+
+    info!("Signing {:?}...", exe.file_name().unwrap_or_default());
+    let mut sign = Command::new("signtool");
+    sign.arg("sign")
+        .arg("/fd")
+        .arg("sha256")
+        .arg("/tr")
+        .arg(&config.timestamp_url)
+        .arg("/td")
+        .arg("sha256");
+    match &certificate {
+        Certificate::File { path, password } => {
+            sign.arg("/f").arg(path);
+            if let Some(password) = password {
+                sign.arg("/p").arg(password);
+            }
+        }
+        Certificate::Thumbprint(thumbprint) => {
+            sign.arg("/sha1").arg(thumbprint);
+        }
+    }
+    sign.arg(exe);
+    // Not traced like other commands, the arguments may contain the password.
+    trace!("Running signtool sign {:?}", exe);
+    let output = sign.output().context("Failed to run signtool, is the Windows SDK installed?")?;
+    if !output.status.success() {
+        anyhow::bail!("signtool sign failed:\n{}", String::from_utf8_lossy(&output.stdout).trim());
+    }
+
+    let mut verify = Command::new("signtool");
+    verify.arg("verify").arg("/pa").arg(exe);
+    crate::trace_command(&verify);
+    let output = verify.output().context("Failed to run signtool verify")?;
+    if !output.status.success() {
+        anyhow::bail!("signtool verify rejected {:?}:\n{}", exe, String::from_utf8_lossy(&output.stdout).trim());
+    }
+
+    info!("Signed and verified {:?}", exe);
+    Ok(())
+}
+
+fn certificate(config: &WindowsConfig) -> Option<Certificate> {
+    let path = std::env::var_os("WINDOWS_SIGN_CERTIFICATE").map(PathBuf::from).or(config.certificate.clone());
+    if let Some(path) = path {
+        let password = std::env::var("WINDOWS_SIGN_PASSWORD").ok();
+        return Some(Certificate::File { path, password });
+    }
+    std::env::var("WINDOWS_SIGN_THUMBPRINT")
+        .ok()
+        .or(config.thumbprint.clone())
+        .map(Certificate::Thumbprint)
+}